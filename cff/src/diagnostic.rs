@@ -0,0 +1,189 @@
+//! errors that know where they happened, and how to show them.
+//! readers return a [`ParseError`] for a place in the input,
+//! writers a [`ConvertError`] for a value the output can't hold.
+
+use std::fmt::{self, Display, Write};
+
+/// reading failed at a place in the input.
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// 1-based
+    pub line: usize,
+    /// 1-based, in characters
+    pub column: Option<usize>,
+    pub help: Option<String>,
+}
+
+impl ParseError {
+    pub fn new(message: impl Into<String>, line: usize, column: Option<usize>) -> Self {
+        ParseError { message: message.into(), line, column, help: None }
+    }
+
+    /// at a byte offset into input.
+    pub fn at_offset(message: impl Into<String>, input: &str, offset: usize) -> Self {
+        let mut offset = offset.min(input.len());
+        while !input.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &input[..offset];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+        ParseError::new(message, line, Some(column))
+    }
+
+    /// from a serde error whose message has its own
+    /// "at line x column y" in it.
+    pub fn from_serde(err: impl Display, line: usize, column: usize) -> Self {
+        // a column of 0 is the newline before the line
+        ParseError::new(strip_position(&err.to_string()), line, Some(column.max(1)))
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.message, self.line)?;
+        if let Some(column) = self.column {
+            write!(f, " column {column}")?;
+        }
+        if let Some(help) = &self.help {
+            write!(f, ". {help}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// writing failed on a value the output format can't hold.
+#[derive(Debug, PartialEq)]
+pub struct ConvertError {
+    pub message: String,
+    /// json pointer to the value
+    pub pointer: String,
+    pub help: Option<String>,
+}
+
+impl ConvertError {
+    pub fn new(message: impl Into<String>, pointer: impl Into<String>) -> Self {
+        ConvertError { message: message.into(), pointer: pointer.into(), help: None }
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help = Some(help.into());
+        self
+    }
+}
+
+impl Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ConvertError {}
+
+/// removes serde's " at line x column y" from a message,
+/// wherever (and however many times) it comes up.
+fn strip_position(message: &str) -> String {
+    let mut out = String::new();
+    let mut rest = message;
+    while let Some(start) = rest.find(" at line ") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + " at line ".len()..];
+        let digits = after.trim_start_matches(|c: char| c.is_ascii_digit());
+        if digits.len() == after.len() {
+            // not a position after all
+            out.push_str(" at line ");
+            rest = after;
+            continue;
+        }
+        rest = match digits.strip_prefix(" column ") {
+            Some(column) => column.trim_start_matches(|c: char| c.is_ascii_digit()),
+            None => digits,
+        };
+    }
+    out.push_str(rest);
+    out
+}
+
+/// the error as it's shown to people: the file, line and
+/// column, the line itself with a caret under the spot, and
+/// any help. other errors are left as they are.
+pub fn render(err: anyhow::Error, file: &str, input: &[u8]) -> anyhow::Error {
+    let mut out = String::new();
+    if let Some(parse) = err.downcast_ref::<ParseError>() {
+        let _ = write!(out, "{}\n --> {file}:{}", parse.message, parse.line);
+        if let Some(column) = parse.column {
+            let _ = write!(out, ":{column}");
+        }
+        let text = String::from_utf8_lossy(input);
+        if let Some(source) = text.lines().nth(parse.line - 1) {
+            let number = parse.line.to_string();
+            let pad = " ".repeat(number.len());
+            // tabs would throw the caret off
+            let source = source.replace('\t', " ");
+            let _ = write!(out, "\n{pad} |\n{number} | {source}");
+            if let Some(column) = parse.column {
+                let _ = write!(out, "\n{pad} | {}^", " ".repeat(column - 1));
+            }
+        }
+        if let Some(help) = &parse.help {
+            let _ = write!(out, "\n  = help: {help}");
+        }
+    } else if let Some(convert) = err.downcast_ref::<ConvertError>() {
+        let pointer = match convert.pointer.as_str() {
+            "" => "the top level",
+            pointer => pointer,
+        };
+        let _ = write!(out, "{}\n --> {pointer} in {file}", convert.message);
+        if let Some(help) = &convert.help {
+            let _ = write!(out, "\n  = help: {help}");
+        }
+    } else {
+        return err;
+    }
+    anyhow::anyhow!(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn positions() {
+        let err = ParseError::at_offset("oops", "ab\ncé\td", 6);
+        assert_eq!((err.line, err.column), (2, Some(3)));
+        assert_eq!(err.to_string(), "oops at line 2 column 3");
+        assert_eq!(strip_position("expected value at line 2 column 7"), "expected value");
+        assert_eq!(strip_position("bad node at line 2 column 4, while parsing"), "bad node, while parsing");
+        assert_eq!(
+            strip_position("did not find expected ',' or ']' at line 2 column 1, while parsing a flow sequence at line 1 column 4"),
+            "did not find expected ',' or ']', while parsing a flow sequence",
+        );
+        assert_eq!(strip_position("stopped at line breaks"), "stopped at line breaks");
+    }
+
+    #[test]
+    fn render_parse_error() {
+        let err = ParseError::new("expected value", 2, Some(8)).with_help("try this");
+        let rendered = render(err.into(), "a.json", b"{\n  \"a\": ,\n}").to_string();
+        assert_eq!(rendered, "expected value\n --> a.json:2:8\n  |\n2 |   \"a\": ,\n  |        ^\n  = help: try this");
+
+        let err = ParseError::new("expected key = value", 1, None);
+        assert_eq!(render(err.into(), "a.ini", b"oops").to_string(), "expected key = value\n --> a.ini:1\n  |\n1 | oops");
+    }
+
+    #[test]
+    fn render_convert_error() {
+        let err = ConvertError::new("toml has no null", "/a/0").with_help("remove it");
+        assert_eq!(render(err.into(), "<stdin>", b"").to_string(), "toml has no null\n --> /a/0 in <stdin>\n  = help: remove it");
+        let other = render(anyhow::anyhow!("plain"), "a.json", b"");
+        assert_eq!(other.to_string(), "plain");
+    }
+}
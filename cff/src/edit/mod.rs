@@ -0,0 +1,79 @@
+//! toml and yaml edited where they're written rather than read into
+//! a [`Value`](crate::Value) and written back out, so comments, key
+//! order and layout survive. used when a file is written as its own
+//! format.
+
+use std::fmt;
+
+use anyhow::Result;
+
+use crate::path::Segment;
+use crate::{Format, Options, Value};
+
+mod toml;
+mod yaml;
+
+pub enum Document {
+    Toml(toml_edit::DocumentMut),
+    Yaml(yaml::Document),
+}
+
+impl Document {
+    /// none for formats that can't be edited this way.
+    pub fn parse(format: Format, input: &[u8]) -> Result<Option<Document>> {
+        let input = std::str::from_utf8(input)?;
+        Ok(match format {
+            Format::Toml => Some(Document::Toml(input.parse()?)),
+            Format::Yaml => Some(Document::Yaml(yaml::Document::parse(input))),
+            _ => None,
+        })
+    }
+
+    pub fn sort_keys(&mut self) -> Result<()> {
+        match self {
+            Document::Toml(doc) => toml::sort_keys(doc),
+            Document::Yaml(doc) => doc.sort_keys()?,
+        }
+        Ok(())
+    }
+
+    /// makes what's at location what it is in root, which has
+    /// had the change made to it already.
+    pub fn set(&mut self, location: &[Segment], root: &Value) -> Result<()> {
+        match self {
+            Document::Toml(doc) => {
+                // for the errors, and so what's written can't fail
+                Format::Toml.write(root, &Options::default())?;
+                toml::set(doc, location, root);
+            }
+            Document::Yaml(doc) => doc.set(location, root),
+        }
+        Ok(())
+    }
+
+    /// takes out what's at location. root has had it taken out
+    /// already.
+    pub fn remove(&mut self, location: &[Segment], root: &Value) {
+        match self {
+            Document::Toml(doc) => toml::remove(doc, location),
+            Document::Yaml(doc) => doc.remove(location, root),
+        }
+    }
+
+    /// tidies the whitespace, leaving comments where they are.
+    pub fn normalize(&mut self) {
+        match self {
+            Document::Toml(doc) => toml::normalize(doc),
+            Document::Yaml(doc) => doc.normalize(),
+        }
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Document::Toml(doc) => write!(f, "{doc}"),
+            Document::Yaml(doc) => write!(f, "{doc}"),
+        }
+    }
+}
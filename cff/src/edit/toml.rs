@@ -0,0 +1,470 @@
+//! toml through toml_edit, which keeps the comments and blank
+//! lines around each key and table header in its decor.
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use toml_edit::{ArrayOfTables, Decor, DocumentMut, InlineTable, Item, KeyMut, Table, Value};
+
+use crate::path::Segment;
+
+/// sorts the keys of every table, and the tables themselves.
+/// comments above a key or header move with it.
+pub fn sort_keys(doc: &mut DocumentMut) {
+    // a comment at the top, set apart by a blank line, is about
+    // the file rather than the first key, so it stays there
+    if let Some(Some(header)) = first_decor(doc.as_table_mut(), split_header) {
+        let prefix = doc.decor().prefix().and_then(|p| p.as_str()).unwrap_or_default();
+        let prefix = format!("{prefix}{header}");
+        doc.decor_mut().set_prefix(prefix);
+    }
+    // so do the blank lines between tables
+    let mut headers = Vec::new();
+    headers_of(doc.as_table(), &mut headers, &mut 0);
+    headers.sort_by_key(|(position, _)| *position);
+    sort_table(doc.as_table_mut());
+    place(doc.as_table_mut(), &mut 0, &mut headers.into_iter().map(|(_, gap)| gap));
+}
+
+fn sort_table(table: &mut Table) {
+    // and between keys
+    let inline = |item: &Item| item.is_value() || matches!(item, Item::Table(table) if table.is_dotted());
+    let gaps: Vec<String> = table.iter_mut().filter(|(_, item)| inline(item)).map(|(key, _)| gap(key.leaf_decor())).collect();
+    table.sort_values();
+    let keys = table.iter_mut().filter(|(_, item)| inline(item));
+    for ((mut key, _), gap) in keys.zip(gaps) {
+        set_gap(key.leaf_decor_mut(), &gap);
+    }
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(table) => sort_table(table),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(sort_table),
+            Item::Value(value) => sort_value(value),
+            Item::None => {}
+        }
+    }
+}
+
+fn sort_value(value: &mut Value) {
+    match value {
+        Value::InlineTable(table) => {
+            // the space before the } stays at the end
+            let decors: Vec<Decor> = table.iter_mut().map(|(_, value)| value.decor().clone()).collect();
+            table.sort_values();
+            for ((_, value), decor) in table.iter_mut().zip(decors) {
+                let suffix = decor.suffix().cloned().unwrap_or_default();
+                value.decor_mut().set_suffix(suffix);
+            }
+            table.iter_mut().for_each(|(_, value)| sort_value(value));
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_value),
+        _ => {}
+    }
+}
+
+/// the position and leading blank lines of each table header
+/// below table, the way they're ordered when written.
+fn headers_of(table: &Table, headers: &mut Vec<(usize, String)>, last: &mut usize) {
+    for (_, item) in table.iter() {
+        let tables: Vec<&Table> = match item {
+            Item::Table(table) if !table.is_dotted() => vec![table],
+            Item::ArrayOfTables(tables) => tables.iter().collect(),
+            _ => continue,
+        };
+        for table in tables {
+            // tables without one follow the one before
+            *last = table.position().unwrap_or(*last);
+            if !table.is_implicit() {
+                headers.push((*last, gap(table.decor())));
+            }
+            headers_of(table, headers, last);
+        }
+    }
+}
+
+/// headers are written in the order of their positions, so
+/// they're renumbered in the (sorted) order of the keys, and
+/// given the gaps the headers in those places had.
+fn place(table: &mut Table, position: &mut usize, gaps: &mut impl Iterator<Item = String>) {
+    if !table.is_dotted() {
+        if *position > 0 && !table.is_implicit() {
+            set_gap(table.decor_mut(), &gaps.next().unwrap_or_default());
+        }
+        table.set_position(*position);
+        *position += 1;
+    }
+    for (_, item) in table.iter_mut() {
+        match item {
+            Item::Table(table) => place(table, position, gaps),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(|table| place(table, position, gaps)),
+            _ => {}
+        }
+    }
+}
+
+/// the blank lines a prefix starts with.
+fn gap(decor: &Decor) -> String {
+    let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or_default();
+    prefix.split_inclusive('\n').take_while(|line| line.trim().is_empty() && line.ends_with('\n')).collect()
+}
+
+fn set_gap(decor: &mut Decor, gap: &str) {
+    let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or_default();
+    let prefix = format!("{gap}{}", &prefix[self::gap(decor).len()..]);
+    decor.set_prefix(prefix);
+}
+
+/// the decor of whatever comes first in the file: the first
+/// key, or the first table header if there are no top-level keys.
+fn first_decor<R>(table: &mut Table, f: impl FnOnce(&mut Decor) -> R) -> Option<R> {
+    let (mut key, item) = table.iter_mut().next()?;
+    match item {
+        Item::Table(table) if table.is_implicit() => first_decor(table, f),
+        Item::Table(table) if !table.is_dotted() => Some(f(table.decor_mut())),
+        Item::ArrayOfTables(tables) => Some(f(tables.get_mut(0)?.decor_mut())),
+        _ => Some(f(key.leaf_decor_mut())),
+    }
+}
+
+/// takes the comments up to the last blank line out of a prefix.
+fn split_header(decor: &mut Decor) -> Option<String> {
+    let prefix = decor.prefix()?.as_str()?.to_string();
+    let mut end = None;
+    let mut at = 0;
+    for line in prefix.split_inclusive('\n') {
+        at += line.len();
+        if line.trim().is_empty() && line.ends_with('\n') {
+            end = Some(at);
+        }
+    }
+    let end = end?;
+    if !prefix[..end].contains('#') {
+        return None;
+    }
+    decor.set_prefix(&prefix[end..]);
+    Some(prefix[..end].to_string())
+}
+
+/// one space around each `=`, one before a trailing comment, no
+/// indentation, no trailing whitespace and no more than one blank
+/// line in a row. comments, arrays and inline tables are left as
+/// they are written.
+pub fn normalize(doc: &mut DocumentMut) {
+    tidy_table(doc.as_table_mut(), false);
+    let prefix = tidy(doc.decor().prefix().and_then(|p| p.as_str()).unwrap_or_default());
+    doc.decor_mut().set_prefix(prefix.trim_start_matches('\n'));
+    // blank lines at the very top go too
+    if prefix.trim().is_empty() {
+        first_decor(doc.as_table_mut(), |decor| {
+            let prefix = decor.prefix().and_then(|p| p.as_str()).unwrap_or_default().trim_start_matches('\n').to_string();
+            decor.set_prefix(prefix);
+        });
+    }
+    let trailing = tidy(doc.trailing().as_str().unwrap_or_default());
+    doc.set_trailing(trailing.trim_end_matches('\n'));
+}
+
+/// dotted is whether table holds the rest of a dotted key, like
+/// the b of `a.b = 1`.
+fn tidy_table(table: &mut Table, dotted: bool) {
+    if !dotted && !table.is_implicit() {
+        let decor = table.decor_mut();
+        let prefix = tidy(decor.prefix().and_then(|p| p.as_str()).unwrap_or_default());
+        let suffix = tidy_suffix(decor.suffix().and_then(|s| s.as_str()).unwrap_or_default());
+        decor.set_prefix(prefix);
+        decor.set_suffix(suffix);
+    }
+    for (mut key, item) in table.iter_mut() {
+        match item {
+            Item::Value(value) => {
+                tidy_key(&mut key, !dotted, true);
+                let decor = value.decor_mut();
+                let suffix = tidy_suffix(decor.suffix().and_then(|s| s.as_str()).unwrap_or_default());
+                decor.set_prefix(" ");
+                decor.set_suffix(suffix);
+            }
+            Item::Table(table) if table.is_dotted() => {
+                tidy_key(&mut key, !dotted, false);
+                tidy_table(table, true);
+            }
+            Item::Table(table) => tidy_table(table, false),
+            Item::ArrayOfTables(tables) => tables.iter_mut().for_each(|table| tidy_table(table, false)),
+            Item::None => {}
+        }
+    }
+}
+
+/// first is whether the key starts its line, last whether
+/// the `=` comes after it.
+fn tidy_key(key: &mut KeyMut, first: bool, last: bool) {
+    let decor = key.leaf_decor_mut();
+    let prefix = match first {
+        true => tidy(decor.prefix().and_then(|p| p.as_str()).unwrap_or_default()),
+        false => String::new(),
+    };
+    decor.set_prefix(prefix);
+    decor.set_suffix(if last { " " } else { "" });
+    key.dotted_decor_mut().clear();
+}
+
+/// the comment and blank lines before a key or header,
+/// without indentation or runs of blank lines.
+fn tidy(prefix: &str) -> String {
+    let mut out = String::new();
+    let mut blank = false;
+    // what's after the last newline is the indentation
+    let lines = prefix.split('\n').collect::<Vec<_>>();
+    for line in &lines[..lines.len() - 1] {
+        let line = line.trim();
+        if line.is_empty() && blank {
+            continue;
+        }
+        blank = line.is_empty();
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+/// what follows a value or header on its line:
+/// nothing, or a comment.
+fn tidy_suffix(suffix: &str) -> String {
+    match suffix.find('#') {
+        Some(start) => format!(" {}", suffix[start..].trim_end()),
+        None => String::new(),
+    }
+}
+
+/// makes what's at location what it is in root, which has had
+/// the change made to it already. a value that's replaced keeps
+/// the comment after it.
+pub fn set(doc: &mut DocumentMut, location: &[Segment], root: &crate::Value) {
+    match location.is_empty() {
+        true => replace_table(doc.as_table_mut(), root),
+        false => set_table(doc.as_table_mut(), location, root),
+    }
+}
+
+/// value is what table holds after the change.
+fn set_table(table: &mut Table, location: &[Segment], value: &crate::Value) {
+    let (Some((Segment::Key(key), rest)), crate::Value::Object(map)) = (location.split_first(), value) else {
+        return;
+    };
+    let Some(child) = map.get(key) else {
+        return;
+    };
+    match table.get_mut(key) {
+        Some(item) if rest.is_empty() => replace_item(item, child),
+        Some(item) => set_item(item, rest, child),
+        None => {
+            table.insert(key, new_item(child));
+        }
+    }
+}
+
+fn set_item(item: &mut Item, location: &[Segment], value: &crate::Value) {
+    match item {
+        Item::Table(table) => set_table(table, location, value),
+        Item::Value(v) => set_value(v, location, value),
+        Item::ArrayOfTables(tables) => {
+            let (Some((Segment::Index(i), rest)), crate::Value::Array(items)) = (location.split_first(), value) else {
+                return;
+            };
+            let Some(child) = items.get(*i) else {
+                return;
+            };
+            match tables.get_mut(*i) {
+                Some(table) if rest.is_empty() => replace_table(table, child),
+                Some(table) => set_table(table, rest, child),
+                None => match child {
+                    crate::Value::Object(map) => tables.push(new_table(map)),
+                    _ => *item = new_item(value),
+                },
+            }
+        }
+        Item::None => *item = new_item(value),
+    }
+}
+
+fn set_value(v: &mut Value, location: &[Segment], value: &crate::Value) {
+    let Some((segment, rest)) = location.split_first() else {
+        return replace_value(v, value);
+    };
+    match (v, segment, value) {
+        (Value::InlineTable(table), Segment::Key(key), crate::Value::Object(map)) => {
+            let Some(child) = map.get(key) else {
+                return;
+            };
+            match table.get_mut(key) {
+                Some(v) => set_value(v, rest, child),
+                None => {
+                    table.insert(key, new_value(child));
+                }
+            }
+        }
+        (Value::Array(array), Segment::Index(i), crate::Value::Array(items)) => {
+            let Some(child) = items.get(*i) else {
+                return;
+            };
+            match array.get_mut(*i) {
+                Some(v) => set_value(v, rest, child),
+                None => array.push(new_value(child)),
+            }
+        }
+        (v, _, value) => replace_value(v, value),
+    }
+}
+
+/// keeps the table's comments and place in the file.
+fn replace_table(table: &mut Table, value: &crate::Value) {
+    let crate::Value::Object(map) = value else {
+        return;
+    };
+    let mut new = new_table(map);
+    *new.decor_mut() = table.decor().clone();
+    if let Some(position) = table.position() {
+        new.set_position(position);
+    }
+    *table = new;
+}
+
+fn replace_item(item: &mut Item, value: &crate::Value) {
+    match (item, value) {
+        (Item::Table(table), crate::Value::Object(_)) => replace_table(table, value),
+        (Item::Value(v), _) => replace_value(v, value),
+        (item, value) => *item = new_item(value),
+    }
+}
+
+fn replace_value(v: &mut Value, value: &crate::Value) {
+    let decor = v.decor().clone();
+    *v = new_value(value);
+    *v.decor_mut() = decor;
+}
+
+/// objects become tables with a [header], and arrays of
+/// objects arrays of tables.
+fn new_item(value: &crate::Value) -> Item {
+    match value {
+        crate::Value::Object(map) => Item::Table(new_table(map)),
+        crate::Value::Array(items) if !items.is_empty() && items.iter().all(|item| matches!(item, crate::Value::Object(_))) => {
+            let mut tables = ArrayOfTables::new();
+            for item in items {
+                if let crate::Value::Object(map) = item {
+                    tables.push(new_table(map));
+                }
+            }
+            Item::ArrayOfTables(tables)
+        }
+        _ => Item::Value(new_value(value)),
+    }
+}
+
+fn new_table(map: &indexmap::IndexMap<String, crate::Value>) -> Table {
+    let mut table = Table::new();
+    for (key, value) in map {
+        table.insert(key, new_item(value));
+    }
+    // no header when it only holds other tables
+    table.set_implicit(!map.is_empty() && table.iter().all(|(_, item)| !item.is_value()));
+    table
+}
+
+fn new_value(value: &crate::Value) -> Value {
+    match value {
+        crate::Value::Bool(b) => (*b).into(),
+        // the toml writer has turned away anything bigger
+        crate::Value::Integer(i) => (*i as i64).into(),
+        crate::Value::Float(f) => (*f).into(),
+        crate::Value::String(s) => s.into(),
+        crate::Value::Bytes(b) => STANDARD.encode(b).into(),
+        crate::Value::Array(items) => Value::Array(items.iter().map(new_value).collect()),
+        crate::Value::Object(map) => Value::InlineTable(map.iter().map(|(key, value)| (key, new_value(value))).collect::<InlineTable>()),
+        crate::Value::Null => unreachable!("the toml writer turns nulls away"),
+    }
+}
+
+/// takes out what's at location, comments and all.
+pub fn remove(doc: &mut DocumentMut, location: &[Segment]) {
+    let Some((last, parent)) = location.split_last() else {
+        return doc.as_table_mut().clear();
+    };
+    let mut item = doc.as_item_mut();
+    for segment in parent {
+        let next = match segment {
+            Segment::Key(key) => item.get(key).is_some().then(|| item.get_mut(key)),
+            Segment::Index(i) => item.get(*i).is_some().then(|| item.get_mut(*i)),
+        };
+        let Some(Some(next)) = next else {
+            return;
+        };
+        item = next;
+    }
+    match (item, last) {
+        (Item::Table(table), Segment::Key(key)) => {
+            table.remove(key);
+        }
+        (Item::Value(Value::InlineTable(table)), Segment::Key(key)) => {
+            table.remove(key);
+        }
+        (Item::Value(Value::Array(array)), Segment::Index(i)) if *i < array.len() => {
+            array.remove(*i);
+        }
+        (Item::ArrayOfTables(tables), Segment::Index(i)) if *i < tables.len() => tables.remove(*i),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(input: &str) -> DocumentMut {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn sorts_with_comments() {
+        let mut toml = doc("# settings\n\n# the name\nname = \"cff\"\n# first\nauthors = [\"a\"]\n\n[package]\nversion = \"1\" # semver\nedition = \"2021\"\n\n# deps\n[dependencies]\nserde = \"1\"\nanyhow = \"1\"\n");
+        sort_keys(&mut toml);
+        assert_eq!(toml.to_string(), "# settings\n\n# first\nauthors = [\"a\"]\n# the name\nname = \"cff\"\n\n# deps\n[dependencies]\nanyhow = \"1\"\nserde = \"1\"\n\n[package]\nedition = \"2021\"\nversion = \"1\" # semver\n");
+    }
+
+    #[test]
+    fn sorts_nested_tables() {
+        let mut toml = doc("[b.y]\nk = 1\n[a]\nz = { d = 1, c = 2 }\n[b.x]\nk = 2\n[[c]]\nb = 1\na = 2\n");
+        sort_keys(&mut toml);
+        assert_eq!(toml.to_string(), "[a]\nz = { c = 2, d = 1 }\n[b.x]\nk = 2\n[b.y]\nk = 1\n[[c]]\na = 2\nb = 1\n");
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        let mut toml = doc("\n\n# top   \nname=\"cff\"   # the name  \n  a.b   =   1\n\n\n\n[ table ]   # t\n    # about x\n    x  =  [1,  2]\n\n\n");
+        normalize(&mut toml);
+        assert_eq!(toml.to_string(), "# top\nname = \"cff\" # the name\na.b = 1\n\n[ table ] # t\n# about x\nx = [1,  2]\n");
+    }
+
+    #[test]
+    fn sets_and_removes_with_comments() {
+        let input = "# top\n[package]\nname = \"cff\"   # the binary\nversion = \"1\"\n\n# deps\n[dependencies]\nclap = { version = \"4\", features = [\"derive\"] }\n";
+        let mut root = crate::Format::Toml.read(input.as_bytes(), &Default::default()).unwrap();
+        let mut toml = doc(input);
+        let key = |key: &str| Segment::Key(key.to_string());
+        let mut change = |location: Vec<Segment>, new: Option<crate::Value>| {
+            match new {
+                Some(new) => {
+                    crate::path::set(&mut root, &location, new).unwrap();
+                    set(&mut toml, &location, &root);
+                }
+                None => {
+                    crate::path::remove(&mut root, &location);
+                    remove(&mut toml, &location);
+                }
+            }
+        };
+        change(vec![key("package"), key("name")], Some(crate::Value::String("cffx".into())));
+        change(vec![key("dependencies"), key("clap"), key("features"), Segment::Index(1)], Some(crate::Value::String("env".into())));
+        change(vec![key("package"), key("version")], None);
+        change(vec![key("tool"), key("x")], Some(crate::Value::Bool(true)));
+        assert_eq!(toml.to_string(), "# top\n[package]\nname = \"cffx\"   # the binary\n\n# deps\n[dependencies]\nclap = { version = \"4\", features = [\"derive\", \"env\"] }\n\n[tool]\nx = true\n");
+    }
+}
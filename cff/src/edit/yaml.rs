@@ -0,0 +1,706 @@
+//! yaml edited as lines. block mappings and sequences are split
+//! into entries, each taking the comments right above it, so they
+//! can be moved around without touching anything else. scalars,
+//! flow collections and anything else are kept as the text they
+//! were written as.
+
+use std::fmt;
+use std::mem::take;
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use crate::formats::pointer;
+use crate::path::Segment;
+use crate::Value;
+
+/// a yaml file, split into its documents.
+pub struct Document {
+    parts: Vec<Part>,
+    /// whether the last line ended with a newline
+    newline: bool,
+}
+
+enum Part {
+    /// a `---` or `...` line
+    Separator(String),
+    Body(Block),
+}
+
+enum Block {
+    Mapping(Entries),
+    Sequence(Entries),
+    Text(Vec<String>),
+}
+
+struct Entries {
+    indent: usize,
+    /// blank lines and comments before the first entry's own comments
+    lead: Vec<String>,
+    entries: Vec<Entry>,
+}
+
+struct Entry {
+    /// the comment lines right above it
+    comments: Vec<String>,
+    /// the `key: ...` or `- ...` line. none for a sequence item
+    /// whose mapping starts on the dash's line, which is then the
+    /// first line of the value, with a space for the dash.
+    head: Option<String>,
+    key: Option<String>,
+    value: Block,
+    /// blank lines and comments up to the next entry
+    after: Vec<String>,
+}
+
+impl Document {
+    pub fn parse(input: &str) -> Document {
+        let mut parts = Vec::new();
+        let mut lines = Vec::new();
+        for line in input.lines() {
+            if is_separator(line) {
+                parts.push(Part::Body(parse(take(&mut lines))));
+                parts.push(Part::Separator(line.to_string()));
+            } else {
+                lines.push(line.to_string());
+            }
+        }
+        parts.push(Part::Body(parse(lines)));
+        Document { parts, newline: input.ends_with('\n') }
+    }
+
+    /// sorts the keys of every block mapping. sequences keep
+    /// their order. an error if an alias would end up above its
+    /// anchor, or a mapping is written in a way that can't be
+    /// sorted line by line.
+    pub fn sort_keys(&mut self) -> Result<()> {
+        for part in &mut self.parts {
+            if let Part::Body(block) = part {
+                block.sort_keys();
+            }
+        }
+        // the sorted text is read back to see that it all worked
+        let sorted = self.to_string();
+        let docs = serde_yaml::Deserializer::from_str(&sorted)
+            .map(Value::deserialize)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| anyhow!("can't sort the keys without moving an alias above its anchor ({err})"))?;
+        for (n, doc) in docs.iter().enumerate() {
+            let mut path = if docs.len() > 1 { vec![n.to_string()] } else { Vec::new() };
+            if let Some(at) = unsorted(doc, &mut path) {
+                return Err(anyhow!("can't sort the keys at {at}, as they're in a flow mapping or have a complex key. convert to json and back to sort them, losing the comments"));
+            }
+        }
+        Ok(())
+    }
+
+    /// one space after each `:` and `-`, comments indented with
+    /// what they're about, no trailing whitespace and no more than
+    /// one blank line in a row. the text of multi-line scalars is
+    /// left alone.
+    pub fn normalize(&mut self) {
+        for part in &mut self.parts {
+            if let Part::Body(block) = part {
+                block.tidy();
+            }
+        }
+        let mut out: Vec<String> = Vec::new();
+        for (line, verbatim) in self.lines() {
+            if verbatim {
+                out.push(line);
+                continue;
+            }
+            let line = line.trim_end();
+            let top = out.last().is_none_or(|last| is_separator(last));
+            if line.is_empty() && (top || out.last().is_some_and(|last| last.is_empty())) {
+                continue;
+            }
+            if is_separator(line) {
+                while out.last().is_some_and(|last| last.is_empty()) {
+                    out.pop();
+                }
+            }
+            out.push(line.to_string());
+        }
+        while out.last().is_some_and(|last| last.is_empty()) {
+            out.pop();
+        }
+        *self = Document::parse(&(out.join("\n") + "\n"));
+    }
+
+    /// makes what's at location what it is in root, which has had
+    /// the change made to it already. a value that's replaced keeps
+    /// the comment on its line.
+    pub fn set(&mut self, location: &[Segment], root: &Value) {
+        self.edit(location, root, false);
+    }
+
+    /// takes out what's at location, with the comments above it.
+    pub fn remove(&mut self, location: &[Segment], root: &Value) {
+        self.edit(location, root, true);
+    }
+
+    fn edit(&mut self, location: &[Segment], root: &Value, remove: bool) {
+        let mut bodies: Vec<&mut Block> = self.parts.iter_mut().filter_map(|part| match part {
+            Part::Body(block) => Some(block),
+            Part::Separator(_) => None,
+        }).collect();
+        let used: Vec<usize> = (0..bodies.len()).filter(|&n| !bodies[n].is_empty()).collect();
+        let (n, location, value) = match used.len() > 1 {
+            // more than one document reads as an array of them
+            true => {
+                let (Some((Segment::Index(i), rest)), Value::Array(docs)) = (location.split_first(), root) else {
+                    return;
+                };
+                match (used.get(*i), docs.get(*i)) {
+                    (Some(&n), value) => (n, rest, value),
+                    (None, Some(value)) => {
+                        self.parts.push(Part::Separator("---".to_string()));
+                        self.parts.push(Part::Body(parse(render(value, 0))));
+                        return;
+                    }
+                    (None, None) => return,
+                }
+            }
+            false => (used.first().copied().unwrap_or(0), location, Some(root)),
+        };
+        let block = &mut *bodies[n];
+        match value {
+            _ if location.is_empty() && remove => *block = Block::Text(Vec::new()),
+            Some(value) if location.is_empty() || !block.edit(location, value, remove) => *block = parse(render(value, 0)),
+            _ => {}
+        }
+    }
+
+    /// each line, and whether it's part of a scalar's text.
+    fn lines(&self) -> Vec<(String, bool)> {
+        let mut out = Vec::new();
+        for part in &self.parts {
+            match part {
+                Part::Separator(line) => out.push((line.clone(), false)),
+                Part::Body(block) => block.render(&mut out),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let lines = self.lines();
+        for (i, (line, _)) in lines.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{line}")?;
+        }
+        if self.newline && !lines.is_empty() {
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl Block {
+    fn sort_keys(&mut self) {
+        match self {
+            Block::Mapping(mapping) => {
+                // the gaps between entries stay where they are
+                let mut gaps: Vec<Vec<String>> = mapping.entries.iter_mut().map(|entry| take(&mut entry.after)).collect();
+                mapping.entries.sort_by(|a, b| a.key.cmp(&b.key));
+                for (entry, gap) in mapping.entries.iter_mut().zip(&mut gaps) {
+                    entry.after = take(gap);
+                }
+                mapping.entries.iter_mut().for_each(|entry| entry.value.sort_keys());
+            }
+            Block::Sequence(sequence) => sequence.entries.iter_mut().for_each(|entry| entry.value.sort_keys()),
+            Block::Text(_) => {}
+        }
+    }
+
+    /// blank lines and comments only, which reads as null.
+    fn is_empty(&self) -> bool {
+        matches!(self, Block::Text(lines) if lines.iter().all(|line| is_blank_or_comment(line)))
+    }
+
+    /// value is what the block holds after the change. false if
+    /// the block can't be edited there, and needs writing out again.
+    fn edit(&mut self, location: &[Segment], value: &Value, remove: bool) -> bool {
+        let Some((segment, rest)) = location.split_first() else {
+            return false;
+        };
+        let (entries, found, child) = match (self, segment, value) {
+            (Block::Mapping(entries), Segment::Key(key), Value::Object(map)) => {
+                let found = entries.entries.iter().position(|entry| entry.key.as_ref() == Some(key));
+                (entries, found, map.get(key))
+            }
+            (Block::Sequence(entries), Segment::Index(i), Value::Array(items)) => {
+                let found = (*i < entries.entries.len()).then_some(*i);
+                (entries, found, items.get(*i))
+            }
+            _ => return false,
+        };
+        match (found, child) {
+            (Some(i), _) if remove && rest.is_empty() => {
+                entries.remove(i);
+                !entries.entries.is_empty()
+            }
+            (Some(i), Some(child)) => {
+                let entry = &mut entries.entries[i];
+                if rest.is_empty() || !entry.value.edit(rest, child, remove) {
+                    entry.replace(child, entries.indent);
+                }
+                true
+            }
+            (None, Some(child)) => entries.add(segment, child),
+            (None, None) => true,
+            (Some(_), None) => false,
+        }
+    }
+
+    fn tidy(&mut self) {
+        let (Block::Mapping(entries) | Block::Sequence(entries)) = self else {
+            return;
+        };
+        let indent = " ".repeat(entries.indent);
+        for entry in &mut entries.entries {
+            for comment in &mut entry.comments {
+                *comment = format!("{indent}{}", comment.trim());
+            }
+            if let Some(head) = &mut entry.head {
+                let content = head.trim_start_matches(' ');
+                let (start, rest) = match key_of(content) {
+                    Some((_, colon)) => (format!("{}:", content[..colon].trim_end()), &content[colon + 1..]),
+                    None => ("-".to_string(), &content[1..]),
+                };
+                let (value, comment) = split_comment(rest);
+                *head = match (value.trim(), comment) {
+                    ("", None) => format!("{indent}{start}"),
+                    ("", Some(comment)) => format!("{indent}{start} {comment}"),
+                    (value, None) => format!("{indent}{start} {value}"),
+                    (value, Some(comment)) => format!("{indent}{start} {value} {comment}"),
+                };
+            }
+            entry.value.tidy();
+        }
+    }
+
+    fn render(&self, out: &mut Vec<(String, bool)>) {
+        let entries = match self {
+            Block::Mapping(entries) | Block::Sequence(entries) => entries,
+            Block::Text(lines) => {
+                out.extend(lines.iter().map(|line| (line.clone(), true)));
+                return;
+            }
+        };
+        let plain = |lines: &[String]| lines.iter().map(|line| (line.clone(), false)).collect::<Vec<_>>();
+        out.extend(plain(&entries.lead));
+        for entry in &entries.entries {
+            out.extend(plain(&entry.comments));
+            match &entry.head {
+                Some(head) => {
+                    out.push((head.clone(), false));
+                    entry.value.render(out);
+                }
+                None => {
+                    let start = out.len();
+                    entry.value.render(out);
+                    // the dash goes back on the mapping's first line
+                    if let Some((line, _)) = out[start..].iter_mut().find(|(line, _)| !is_blank_or_comment(line)) {
+                        line.replace_range(entries.indent..entries.indent + 1, "-");
+                    }
+                }
+            }
+            out.extend(plain(&entry.after));
+        }
+    }
+}
+
+impl Entries {
+    /// a new last entry. the lines after the old last one stay last.
+    fn add(&mut self, segment: &Segment, value: &Value) -> bool {
+        let (lines, sequence) = match segment {
+            Segment::Key(key) => (render(&Value::Object(IndexMap::from([(key.clone(), value.clone())])), self.indent), false),
+            Segment::Index(_) => (render(&Value::Array(vec![value.clone()]), self.indent), true),
+        };
+        let Some(mut new) = entries(&lines, self.indent, sequence) else {
+            return false;
+        };
+        let mut entry = new.entries.remove(0);
+        if let Some(last) = self.entries.last_mut() {
+            entry.after = take(&mut last.after);
+        }
+        self.entries.push(entry);
+        true
+    }
+
+    fn remove(&mut self, i: usize) {
+        let removed = self.entries.remove(i);
+        if i == self.entries.len() {
+            if let Some(last) = self.entries.last_mut() {
+                last.after = removed.after;
+            }
+        }
+    }
+}
+
+impl Entry {
+    /// writes value in place of the old one, keeping the key as
+    /// it was written and the comment on its line.
+    fn replace(&mut self, value: &Value, indent: usize) {
+        let pad = " ".repeat(indent);
+        let (start, comment, flow) = match &self.head {
+            Some(head) => {
+                let content = &head[indent..];
+                let (start, rest) = match self.key.as_ref().and_then(|_| key_of(content)) {
+                    Some((_, colon)) => (format!("{}:", content[..colon].trim_end()), &content[colon + 1..]),
+                    None => ("-".to_string(), &content[1..]),
+                };
+                let (value, comment) = split_comment(rest);
+                // the comment stays where it was after a scalar
+                let space = match value.trim() {
+                    "" => " ",
+                    _ => &value[value.trim_end().len()..],
+                };
+                let flow = value.trim_start().starts_with(['[', '{']);
+                (start, comment.map(|comment| format!("{space}{comment}")).unwrap_or_default(), flow)
+            }
+            None => ("-".to_string(), String::new(), false),
+        };
+        let collection = matches!(value, Value::Array(items) if !items.is_empty())
+            || matches!(value, Value::Object(map) if !map.is_empty());
+        let lines = match flow && collection {
+            // json is flow yaml
+            true => vec![serde_json::to_string(value).expect("values always write as json")],
+            false => render(value, 0),
+        };
+        (self.head, self.value) = match &lines[..] {
+            [line] if flow || !collection => (Some(format!("{pad}{start} {line}{comment}")), Block::Text(Vec::new())),
+            [first, rest @ ..] if first.starts_with(['|', '>']) => (Some(format!("{pad}{start} {first}{comment}")), Block::Text(indented(rest, indent))),
+            // - name: x, with the mapping starting on the dash's line
+            _ if start == "-" && comment.is_empty() && matches!(value, Value::Object(_)) => (None, parse(indented(&lines, indent + 2))),
+            _ => (Some(format!("{pad}{start}{comment}")), parse(indented(&lines, indent + 2))),
+        };
+    }
+}
+
+/// where the first mapping with keys out of order is.
+fn unsorted(value: &Value, path: &mut Vec<String>) -> Option<String> {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(map) if !map.keys().is_sorted() => {
+            return Some(if path.is_empty() { "the top level".to_string() } else { pointer(path) });
+        }
+        Value::Object(map) => map.iter().map(|(key, value)| (key.clone(), value)).collect(),
+        Value::Array(items) => items.iter().enumerate().map(|(i, item)| (i.to_string(), item)).collect(),
+        _ => return None,
+    };
+    children.into_iter().find_map(|(key, child)| {
+        path.push(key);
+        let at = unsorted(child, path);
+        path.pop();
+        at
+    })
+}
+
+/// value as yaml lines, indented.
+fn render(value: &Value, indent: usize) -> Vec<String> {
+    let text = serde_yaml::to_string(value).expect("values always write as yaml");
+    indented(&text.lines().map(str::to_string).collect::<Vec<_>>(), indent)
+}
+
+fn indented(lines: &[String], indent: usize) -> Vec<String> {
+    let pad = " ".repeat(indent);
+    lines.iter().map(|line| if line.is_empty() { String::new() } else { format!("{pad}{line}") }).collect()
+}
+
+/// a mapping or sequence if the lines are one, otherwise text.
+fn parse(lines: Vec<String>) -> Block {
+    let Some(first) = lines.iter().find(|line| !is_blank_or_comment(line)) else {
+        return Block::Text(lines);
+    };
+    let indent = indent_of(first);
+    let content = &first[indent..];
+    let sequence = is_dash(content);
+    if !sequence && key_of(content).is_none() {
+        return Block::Text(lines);
+    }
+    match entries(&lines, indent, sequence) {
+        Some(entries) if sequence => Block::Sequence(entries),
+        Some(entries) => Block::Mapping(entries),
+        None => Block::Text(lines),
+    }
+}
+
+/// splits lines into entries at each key (or dash) at indent.
+/// none if there's something there that isn't one.
+fn entries(lines: &[String], indent: usize, sequence: bool) -> Option<Entries> {
+    let mut heads = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        if is_blank_or_comment(line) {
+            continue;
+        }
+        let at = indent_of(line);
+        let content = &line[at..];
+        if at < indent {
+            return None;
+        }
+        // a mapping's value can be a sequence at the same indent
+        if at > indent || (!sequence && is_dash(content)) {
+            continue;
+        }
+        let head = match sequence {
+            true => is_dash(content),
+            false => key_of(content).is_some(),
+        };
+        if !head {
+            return None;
+        }
+        heads.push(i);
+    }
+
+    let mut lead = lines[..heads[0]].to_vec();
+    let mut comments = split_comments(&mut lead, indent);
+    let mut entries = Vec::with_capacity(heads.len());
+    for (n, &head) in heads.iter().enumerate() {
+        let end = heads.get(n + 1).copied().unwrap_or(lines.len());
+        let mut body = lines[head + 1..end].to_vec();
+        let next = match n + 1 < heads.len() {
+            true => split_comments(&mut body, indent),
+            false => Vec::new(),
+        };
+        let after = split_after(&mut body, indent);
+        let mut entry = entry(&lines[head], indent, sequence, body);
+        entry.comments = take(&mut comments);
+        entry.after = after;
+        entries.push(entry);
+        comments = next;
+    }
+    Some(Entries { indent, lead, entries })
+}
+
+fn entry(head: &str, indent: usize, sequence: bool, body: Vec<String>) -> Entry {
+    let content = &head[indent..];
+    let mut entry = Entry { comments: Vec::new(), head: Some(head.to_string()), key: None, value: Block::Text(Vec::new()), after: Vec::new() };
+    let rest = match key_of(content) {
+        Some((key, colon)) if !sequence => {
+            entry.key = Some(key);
+            &content[colon + 1..]
+        }
+        _ => &content[1..],
+    };
+    let inline = rest.trim_start();
+    entry.value = if opens_block(rest) {
+        parse(body)
+    } else if sequence && (is_dash(inline) || key_of(inline).is_some()) {
+        // - name: x, read as if the dash were a space
+        let column = head.len() - inline.len();
+        let mut lines = vec![format!("{}{inline}", " ".repeat(column))];
+        lines.extend(body);
+        entry.head = None;
+        parse(lines)
+    } else {
+        Block::Text(body)
+    };
+    entry
+}
+
+/// takes the comments right above the next entry off the end
+/// of lines.
+fn split_comments(lines: &mut Vec<String>, indent: usize) -> Vec<String> {
+    let count = lines.iter().rev()
+        .take_while(|line| is_comment(line) && indent_of(line) <= indent)
+        .count();
+    lines.split_off(lines.len() - count)
+}
+
+/// takes the blank lines and comments that aren't part of the
+/// value off the end of its lines.
+fn split_after(lines: &mut Vec<String>, indent: usize) -> Vec<String> {
+    let count = lines.iter().rev()
+        .take_while(|line| line.trim().is_empty() || (is_comment(line) && indent_of(line) <= indent))
+        .count();
+    lines.split_off(lines.len() - count)
+}
+
+/// the key of a `key: value` line, without quotes, and where its
+/// colon is. none for anything else, including complex keys.
+fn key_of(content: &str) -> Option<(String, usize)> {
+    let colon_at = |i: usize| content[i..].starts_with(':') && content[i + 1..].chars().next().is_none_or(|c| c == ' ' || c == '\t');
+    let quote = content.chars().next()?;
+    if quote == '"' || quote == '\'' {
+        let mut key = String::new();
+        let mut chars = content.char_indices().skip(1);
+        let end = loop {
+            let (i, c) = chars.next()?;
+            match c {
+                '\\' if quote == '"' => key.extend(chars.next().map(|(_, c)| c)),
+                '\'' if quote == '\'' && content[i + 1..].starts_with('\'') => {
+                    chars.next();
+                    key.push('\'');
+                }
+                c if c == quote => break i + 1,
+                c => key.push(c),
+            }
+        };
+        let colon = end + content[end..].len() - content[end..].trim_start().len();
+        return colon_at(colon).then_some((key, colon));
+    }
+    if is_dash(content) || content.starts_with(['?', '[', '{', '#', '&', '*', '!', '|', '>', '%', '@', '`']) {
+        return None;
+    }
+    let colon = (0..content.len()).find(|&i| content.is_char_boundary(i) && colon_at(i))?;
+    let key = content[..colon].trim_end();
+    if key.is_empty() || key.contains(" #") {
+        return None;
+    }
+    Some((key.to_string(), colon))
+}
+
+/// splits a trailing comment off what follows a key or dash.
+fn split_comment(rest: &str) -> (&str, Option<&str>) {
+    let mut quote = None;
+    let mut prev = ' ';
+    let mut chars = rest.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            // quotes only start a scalar, not in the middle of a word
+            (None, '"' | '\'') if prev.is_whitespace() || "[{,:".contains(prev) => quote = Some(c),
+            (None, '#') if prev.is_whitespace() => return (&rest[..i], Some(rest[i..].trim_end())),
+            _ => {}
+        }
+        prev = c;
+    }
+    (rest, None)
+}
+
+/// whether what follows a key or dash leaves the value to the
+/// lines below: nothing, a comment, or just an anchor or tag.
+fn opens_block(rest: &str) -> bool {
+    rest.split_whitespace()
+        .take_while(|word| !word.starts_with('#'))
+        .all(|word| word.starts_with(['&', '!']))
+}
+
+fn is_dash(content: &str) -> bool {
+    content == "-" || content.starts_with("- ") || content.starts_with("-\t")
+}
+
+fn is_separator(line: &str) -> bool {
+    ["---", "..."].iter().any(|marker| {
+        line.strip_prefix(marker).is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '\t']))
+    })
+}
+
+fn is_comment(line: &str) -> bool {
+    line.trim_start().starts_with('#')
+}
+
+fn is_blank_or_comment(line: &str) -> bool {
+    line.trim().is_empty() || is_comment(line)
+}
+
+fn indent_of(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(input: &str) -> String {
+        let mut doc = Document::parse(input);
+        doc.sort_keys().unwrap();
+        doc.to_string()
+    }
+
+    #[test]
+    fn unchanged_without_edits() {
+        let input = "# top\n\nb: 1  # one\na:\n  - x\n  -   y: [1,\n      2]\n\n---\ntext: |\n  kept   \n\n  as is\n";
+        assert_eq!(Document::parse(input).to_string(), input);
+    }
+
+    #[test]
+    fn sorts_with_comments() {
+        let input = "# config\n\n# the name\nname: cff\n# who\nauthors:\n- b\n- a\n\nbuild:\n  # release\n  profile: release\n  jobs: 4 # cores\n";
+        assert_eq!(sorted(input), "# config\n\n# who\nauthors:\n- b\n- a\nbuild:\n  jobs: 4 # cores\n  # release\n  profile: release\n\n# the name\nname: cff\n");
+    }
+
+    #[test]
+    fn sorts_sequence_items_and_documents() {
+        let input = "containers:\n  - name: web\n    image: nginx\n    ports: [80]\n  -   name: db\n      env:\n        b: 2\n        a: 1\n---\n\"z\": 1\n'y': 2\n";
+        assert_eq!(sorted(input), "containers:\n  - image: nginx\n    name: web\n    ports: [80]\n  -   env:\n        a: 1\n        b: 2\n      name: db\n---\n'y': 2\n\"z\": 1\n");
+    }
+
+    #[test]
+    fn leaves_scalars_alone() {
+        let input = "b: |\n  z: 1\n  a: 2\nurl: http://x\na: >\n  folded: text\n";
+        assert_eq!(sorted(input), "a: >\n  folded: text\nb: |\n  z: 1\n  a: 2\nurl: http://x\n");
+        assert_eq!(sorted("? complex\n: key\n"), "? complex\n: key\n");
+    }
+
+    #[test]
+    fn comments_after_values() {
+        assert_eq!(split_comment(" web   # name "), (" web   ", Some("# name")));
+        assert_eq!(split_comment(" 'a # b' # c"), (" 'a # b' ", Some("# c")));
+        assert_eq!(split_comment(" [\"#\", don't] #x"), (" [\"#\", don't] ", Some("#x")));
+        assert_eq!(split_comment(" a#b"), (" a#b", None));
+    }
+
+    #[test]
+    fn normalizes_whitespace() {
+        let input = "\n\nname:    cff   \n# about deps   \ndeps:   \n  -    a\n\n\n\n  - b   # two\ntext: |\n  trailing   \n\n\n  kept\n\n\n---\n\na:   1\n\n";
+        let mut doc = Document::parse(input);
+        doc.normalize();
+        assert_eq!(doc.to_string(), "name: cff\n# about deps\ndeps:\n  - a\n\n  - b # two\ntext: |\n  trailing   \n\n\n  kept\n---\na: 1\n");
+    }
+
+    #[test]
+    fn sets_and_removes_with_comments() {
+        let input = "# top\nservice:\n  name: web   # public\n  # the ports\n  ports:\n    - 80\n    - 443\n  tags: [a, b]\nitems:\n  - name: x\n    size: 1\n";
+        let mut root = crate::Format::Yaml.read(input.as_bytes(), &Default::default()).unwrap();
+        let mut yaml = Document::parse(input);
+        let key = |key: &str| Segment::Key(key.to_string());
+        let mut change = |yaml: &mut Document, location: Vec<Segment>, new: Option<&str>| {
+            match new {
+                Some(new) => {
+                    crate::path::set(&mut root, &location, serde_json::from_str(new).unwrap()).unwrap();
+                    yaml.set(&location, &root);
+                }
+                None => {
+                    crate::path::remove(&mut root, &location);
+                    yaml.remove(&location, &root);
+                }
+            }
+        };
+        change(&mut yaml, vec![key("service"), key("name")], Some("\"api\""));
+        change(&mut yaml, vec![key("service"), key("ports"), Segment::Index(0)], None);
+        change(&mut yaml, vec![key("service"), key("ports"), Segment::Index(1)], Some("8080"));
+        change(&mut yaml, vec![key("service"), key("tags"), Segment::Index(2)], Some("\"c\""));
+        change(&mut yaml, vec![key("items"), Segment::Index(0), key("size")], Some("{\"w\": 2}"));
+        change(&mut yaml, vec![key("items"), Segment::Index(1)], Some("{\"name\": \"y\"}"));
+        change(&mut yaml, vec![key("env"), key("LOG")], Some("\"stdout\""));
+        assert_eq!(yaml.to_string(), "# top\nservice:\n  name: api   # public\n  # the ports\n  ports:\n    - 443\n    - 8080\n  tags: [\"a\",\"b\",\"c\"]\nitems:\n  - name: x\n    size:\n      w: 2\n  - name: y\nenv:\n  LOG: stdout\n");
+
+        change(&mut yaml, vec![key("items")], None);
+        change(&mut yaml, vec![key("env"), key("LOG")], None);
+        assert!(yaml.to_string().ends_with("  tags: [\"a\",\"b\",\"c\"]\nenv: {}\n"));
+    }
+
+    #[test]
+    fn refuses_what_it_cant_sort() {
+        let err = Document::parse("z: &anc\n  x: 1\na: *anc\n").sort_keys().unwrap_err();
+        assert!(err.to_string().starts_with("can't sort the keys without moving an alias above its anchor"), "{err}");
+        assert_eq!(sorted("a: &anc\n  x: 1\nz: *anc\n"), "a: &anc\n  x: 1\nz: *anc\n");
+
+        let err = Document::parse("list:\n- ? complex\n  : 1\n  b: 2\n  a: 3\n").sort_keys().unwrap_err();
+        assert!(err.to_string().starts_with("can't sort the keys at /list/0,"), "{err}");
+        let err = Document::parse("a: {z: 1, y: 2}\n").sort_keys().unwrap_err();
+        assert!(err.to_string().starts_with("can't sort the keys at /a,"), "{err}");
+    }
+}
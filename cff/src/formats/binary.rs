@@ -0,0 +1,132 @@
+//! formats that aren't text. with --hex or --base64 they're
+//! read from and written as text instead of raw bytes.
+
+use anyhow::{Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+use super::Options;
+use crate::diagnostic::ConvertError;
+use crate::Value;
+
+/// the raw bytes, decoding --hex/--base64 input.
+pub fn decode(input: &[u8], options: &Options) -> Result<Vec<u8>> {
+    if !options.hex && !options.base64 {
+        return Ok(input.to_vec());
+    }
+    let text: String = std::str::from_utf8(input)
+        .with_context(|| "expected hex or base64 text")?
+        .split_whitespace()
+        .collect();
+    if options.hex {
+        hex::decode(&text).with_context(|| "input isn't valid hex")
+    } else {
+        STANDARD.decode(&text).with_context(|| "input isn't valid base64")
+    }
+}
+
+/// the bytes as --hex/--base64 text, if asked for.
+pub fn encode(bytes: Vec<u8>, options: &Options) -> Vec<u8> {
+    if options.hex {
+        hex::encode(bytes).into_bytes()
+    } else if options.base64 {
+        STANDARD.encode(bytes).into_bytes()
+    } else {
+        bytes
+    }
+}
+
+pub mod msgpack {
+    use super::*;
+
+    pub fn read(input: &[u8]) -> Result<Value> {
+        Ok(rmp_serde::from_slice(input)?)
+    }
+
+    pub fn write(value: &Value) -> Result<Vec<u8>> {
+        Ok(rmp_serde::to_vec(value)?)
+    }
+}
+
+pub mod cbor {
+    use super::*;
+
+    pub fn read(input: &[u8]) -> Result<Value> {
+        Ok(ciborium::from_reader(input)?)
+    }
+
+    pub fn write(value: &Value) -> Result<Vec<u8>> {
+        let mut out = Vec::new();
+        ciborium::into_writer(value, &mut out)?;
+        Ok(out)
+    }
+}
+
+pub mod bson {
+    use super::*;
+
+    pub fn read(input: &[u8]) -> Result<Value> {
+        Ok(::bson::from_slice(input)?)
+    }
+
+    pub fn write(value: &Value) -> Result<Vec<u8>> {
+        if !matches!(value, Value::Object(_)) {
+            return Err(ConvertError::new(format!("bson needs an object at the top level, not {}", value.type_name()), "")
+                .with_help(r#"put it under a key, like {"items": ...}, or convert to msgpack or cbor"#)
+                .into());
+        }
+        Ok(::bson::to_vec(value)?)
+    }
+
+    /// a bson document starts with its own length.
+    pub fn looks_like(input: &[u8]) -> bool {
+        input.len() >= 5
+            && input[..4] == (input.len() as u32).to_le_bytes()
+            && input.last() == Some(&0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        serde_json::from_str(r#"{"name": "cff", "n": -3, "big": 18446744073709551615, "f": 1.5, "ok": true, "none": null, "list": [1, "a"]}"#).unwrap()
+    }
+
+    #[test]
+    fn round_trips() {
+        let value = sample();
+        assert_eq!(msgpack::read(&msgpack::write(&value).unwrap()).unwrap(), value);
+        assert_eq!(cbor::read(&cbor::write(&value).unwrap()).unwrap(), value);
+
+        // bson has no unsigned 64 bit ints
+        let Value::Object(mut map) = value else { unreachable!() };
+        map.shift_remove("big");
+        let value = Value::Object(map);
+        let bytes = bson::write(&value).unwrap();
+        assert!(bson::looks_like(&bytes));
+        assert_eq!(bson::read(&bytes).unwrap(), value);
+        assert!(bson::write(&Value::Array(vec![])).is_err(), "top level must be an object");
+    }
+
+    #[test]
+    fn bytes_values() {
+        let value = Value::Bytes(vec![0, 159, 255]);
+        assert_eq!(msgpack::read(&msgpack::write(&value).unwrap()).unwrap(), value);
+        assert_eq!(cbor::read(&cbor::write(&value).unwrap()).unwrap(), value);
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#""AJ//""#, "base64 in text formats");
+    }
+
+    #[test]
+    fn text_encodings() {
+        let hex = Options { hex: true, ..Default::default() };
+        let base64 = Options { base64: true, ..Default::default() };
+        assert_eq!(encode(vec![0x81, 0xa1], &hex), b"81a1");
+        assert_eq!(encode(vec![0x81, 0xa1], &base64), b"gaE=");
+        assert_eq!(decode(b"81 a1\n", &hex).unwrap(), [0x81, 0xa1]);
+        assert_eq!(decode(b"gaE=\n", &base64).unwrap(), [0x81, 0xa1]);
+        assert_eq!(decode(b"\x81\xa1", &Options::default()).unwrap(), [0x81, 0xa1]);
+        assert!(decode(b"zz", &hex).is_err());
+    }
+}
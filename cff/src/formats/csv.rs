@@ -0,0 +1,308 @@
+//! rows are objects: writing flattens nested fields into dotted
+//! column names (`address.city`, `tags.0`) with the header taken from
+//! every key seen, in order. reading gives an array of objects keyed
+//! by the header, or an array of arrays with --no-header. every cell
+//! is a string unless --infer-types is set.
+
+use std::io::{Read, Write};
+
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use indexmap::{IndexMap, IndexSet};
+
+use super::Options;
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str, default_delimiter: u8, options: &Options) -> Result<Value> {
+    Ok(Value::Array(records(input.as_bytes(), default_delimiter, options)?.collect::<Result<_>>()?))
+}
+
+/// the rows one at a time.
+pub fn records(input: impl Read, default_delimiter: u8, options: &Options) -> Result<impl Iterator<Item = Result<Value>>> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .delimiter(delimiter(options, default_delimiter)?)
+        .has_headers(!options.no_header)
+        .flexible(options.no_header)
+        .from_reader(input);
+
+    let infer_types = options.infer_types;
+    let cell = move |s: &str| match infer_types {
+        true => infer(s),
+        false => Value::String(s.to_string()),
+    };
+    let header = match options.no_header {
+        true => None,
+        false => Some(reader.headers().map_err(parse_error)?.clone()),
+    };
+    Ok(reader.into_records().map(move |record| {
+        let record = record.map_err(parse_error)?;
+        let row = match &header {
+            None => Value::Array(record.iter().map(cell).collect()),
+            Some(header) => Value::Object(header.iter()
+                .zip(record.iter())
+                .map(|(key, value)| (key.to_string(), cell(value)))
+                .collect()),
+        };
+        Ok(row)
+    }))
+}
+
+pub fn write(value: &Value, default_delimiter: u8, options: &Options) -> Result<String> {
+    let rows = match value {
+        Value::Array(rows) => rows.as_slice(),
+        // a single object is a single row
+        Value::Object(_) => std::slice::from_ref(value),
+        other => {
+            return Err(ConvertError::new(format!("csv needs an array of objects, not {}", other.type_name()), "")
+                .with_help(r#"put it in an array, like [{"value": ...}], or convert to json or yaml"#)
+                .into());
+        }
+    };
+
+    let mut flat_rows = Vec::with_capacity(rows.len());
+    let mut header = IndexSet::new();
+    for (i, row) in rows.iter().enumerate() {
+        let flat = flat_row(i, row)?;
+        header.extend(flat.keys().cloned());
+        flat_rows.push(flat);
+    }
+
+    let mut writer = ::csv::WriterBuilder::new()
+        .delimiter(delimiter(options, default_delimiter)?)
+        .from_writer(Vec::new());
+    let rows_are_arrays = rows.iter().all(|r| matches!(r, Value::Array(_)));
+    if !options.no_header && !rows_are_arrays {
+        writer.write_record(&header)?;
+    }
+    for flat in flat_rows {
+        writer.write_record(header.iter().map(|key| flat.get(key).map(String::as_str).unwrap_or_default()))?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// writes rows as they come. with nothing buffered the header
+/// can only come from the first row, so later rows can't add
+/// columns.
+pub struct RecordWriter<W: Write> {
+    writer: ::csv::Writer<W>,
+    header: Option<IndexSet<String>>,
+    no_header: bool,
+    rows: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(output: W, default_delimiter: u8, options: &Options) -> Result<Self> {
+        let writer = ::csv::WriterBuilder::new()
+            .delimiter(delimiter(options, default_delimiter)?)
+            .flexible(true)
+            .from_writer(output);
+        Ok(RecordWriter { writer, header: None, no_header: options.no_header, rows: 0 })
+    }
+
+    pub fn write(&mut self, row: &Value) -> Result<()> {
+        let flat = flat_row(self.rows, row)?;
+        self.rows += 1;
+        if let Value::Array(_) = row {
+            self.writer.write_record(flat.values())?;
+            return Ok(());
+        }
+        let header = match &self.header {
+            Some(header) => header,
+            None => {
+                let header = flat.keys().cloned().collect();
+                if !self.no_header {
+                    self.writer.write_record(&header)?;
+                }
+                self.header.insert(header)
+            }
+        };
+        if let Some(key) = flat.keys().find(|key| !header.contains(*key)) {
+            let row = format!("/{}", self.rows - 1);
+            return Err(ConvertError::new(format!("the row at {row} has a {key} column the first row doesn't"), row)
+                .with_help("when streaming the header comes from the first row, so give it every column, or convert without --stream")
+                .into());
+        }
+        self.writer.write_record(header.iter().map(|key| flat.get(key).map(String::as_str).unwrap_or_default()))?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// a row as column name to cell. rows that are
+/// arrays are keyed by index.
+fn flat_row(i: usize, row: &Value) -> Result<IndexMap<String, String>> {
+    let mut flat = IndexMap::new();
+    match row {
+        Value::Object(_) => flatten("", row, &mut flat),
+        // rows given as arrays are written as-is
+        Value::Array(cells) => {
+            for (j, cell) in cells.iter().enumerate() {
+                flatten(&j.to_string(), cell, &mut flat);
+            }
+        }
+        other => {
+            return Err(ConvertError::new(format!("csv rows must be objects or arrays, not {} like the one at /{i}", other.type_name()), format!("/{i}"))
+                .with_help(r#"make each row an object, like {"value": ...}"#)
+                .into());
+        }
+    }
+    Ok(flat)
+}
+
+/// with the line the csv error gives, if any.
+fn parse_error(err: ::csv::Error) -> anyhow::Error {
+    let Some(line) = err.position().map(|pos| pos.line() as usize) else {
+        return err.into();
+    };
+    // drop the "CSV error: record 1 (line: 2, byte: 3): " it starts with
+    let message = err.to_string();
+    let message = message.split_once("): ").map(|(_, m)| m.to_string()).unwrap_or(message);
+    ParseError::new(message, line, None).into()
+}
+
+/// --delimiter if given, otherwise the format's own.
+fn delimiter(options: &Options, default: u8) -> Result<u8> {
+    match options.delimiter {
+        None => Ok(default),
+        Some(c) if c.is_ascii() => Ok(c as u8),
+        Some(c) => Err(anyhow!("delimiter must be a single ascii character, not {c:?}")),
+    }
+}
+
+fn flatten(prefix: &str, value: &Value, out: &mut IndexMap<String, String>) {
+    let key = |k: &str| match prefix {
+        "" => k.to_string(),
+        _ => format!("{prefix}.{k}"),
+    };
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                flatten(&key(k), v, out);
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                flatten(&key(&i.to_string()), v, out);
+            }
+        }
+        Value::Null => {
+            out.insert(prefix.to_string(), String::new());
+        }
+        Value::Bool(b) => {
+            out.insert(prefix.to_string(), b.to_string());
+        }
+        Value::Integer(i) => {
+            out.insert(prefix.to_string(), i.to_string());
+        }
+        Value::Float(f) => {
+            out.insert(prefix.to_string(), f.to_string());
+        }
+        Value::String(s) => {
+            out.insert(prefix.to_string(), s.clone());
+        }
+        Value::Bytes(b) => {
+            out.insert(prefix.to_string(), STANDARD.encode(b));
+        }
+    }
+}
+
+/// empty cells are null, then bools, integers
+/// and floats, and anything else a string.
+pub(super) fn infer(cell: &str) -> Value {
+    let trimmed = cell.trim();
+    if trimmed.is_empty() || trimmed == "null" {
+        return Value::Null;
+    }
+    match trimmed {
+        "true" => return Value::Bool(true),
+        "false" => return Value::Bool(false),
+        _ => {}
+    }
+    // keep things like zip codes as they are
+    let leading_zero = trimmed.len() > 1 && trimmed.trim_start_matches('-').starts_with('0')
+        && !trimmed.trim_start_matches('-').starts_with("0.");
+    if !leading_zero {
+        if let Ok(i) = trimmed.parse::<i128>() {
+            return Value::Integer(i);
+        }
+        if let Ok(f) = trimmed.parse::<f64>() {
+            if f.is_finite() {
+                return Value::Float(f);
+            }
+        }
+    }
+    Value::String(cell.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn write_flattens() {
+        let value = json(r#"[
+            {"name": "a", "address": {"city": "x, y"}, "tags": [1, 2]},
+            {"name": "b", "active": true, "address": null}
+        ]"#);
+        let csv = write(&value, b',', &Options::default()).unwrap();
+        assert_eq!(csv, "name,address.city,tags.0,tags.1,active,address\na,\"x, y\",1,2,,\nb,,,,true,\n");
+
+        let options = Options { no_header: true, ..Default::default() };
+        assert_eq!(write(&json(r#"[{"a": 1}]"#), b'\t', &options).unwrap(), "1\n");
+        assert_eq!(write(&json("[[1, 2], [3]]"), b',', &Options::default()).unwrap(), "1,2\n3,\n", "rows as arrays");
+        let err = write(&json(r#"[{"a": 1}, 2]"#), b',', &Options::default()).unwrap_err();
+        assert_eq!(err.downcast::<ConvertError>().unwrap().pointer, "/1");
+        assert!(write(&json("1"), b',', &Options::default()).is_err());
+    }
+
+    #[test]
+    fn read_rows() {
+        let input = "name\tcount\tok\tzip\na\t3\ttrue\t02134\nb\t1.5\t\t\n";
+        assert_eq!(read(input, b'\t', &Options::default()).unwrap(), json(r#"[
+            {"name": "a", "count": "3", "ok": "true", "zip": "02134"},
+            {"name": "b", "count": "1.5", "ok": "", "zip": ""}
+        ]"#));
+
+        let options = Options { infer_types: true, ..Default::default() };
+        assert_eq!(read(input, b'\t', &options).unwrap(), json(r#"[
+            {"name": "a", "count": 3, "ok": true, "zip": "02134"},
+            {"name": "b", "count": 1.5, "ok": null, "zip": null}
+        ]"#));
+
+        let options = Options { no_header: true, delimiter: Some(';'), ..Default::default() };
+        assert_eq!(read("a;b\nc\n", b',', &options).unwrap(), json(r#"[["a", "b"], ["c"]]"#));
+    }
+
+    #[test]
+    fn record_writer() {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(&mut out, b',', &Options::default()).unwrap();
+        writer.write(&json(r#"{"a": 1, "b": {"c": 2}}"#)).unwrap();
+        writer.write(&json(r#"{"b": {"c": 3}}"#)).unwrap();
+        let err = writer.write(&json(r#"{"d": 4}"#)).unwrap_err();
+        let err = err.downcast::<ConvertError>().unwrap();
+        assert_eq!((err.message.as_str(), err.pointer.as_str()), ("the row at /2 has a d column the first row doesn't", "/2"));
+        writer.finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "a,b.c\n1,2\n,3\n");
+    }
+
+    #[test]
+    fn infer_cells() {
+        assert_eq!(infer("-12"), Value::Integer(-12));
+        assert_eq!(infer("0"), Value::Integer(0));
+        assert_eq!(infer("0.5"), Value::Float(0.5));
+        assert_eq!(infer("007"), Value::String("007".to_string()));
+        assert_eq!(infer("nan"), Value::String("nan".to_string()));
+        assert_eq!(infer(" "), Value::Null);
+    }
+}
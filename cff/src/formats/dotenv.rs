@@ -0,0 +1,190 @@
+//! `KEY=value` lines, optionally starting with `export`. single
+//! quoted values are taken as they are, double quoted ones can hold
+//! escapes and span lines. variables aren't expanded. there's no
+//! nesting, so only an object of scalars can be written.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+
+use super::{pointer, scalar_text, Options};
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str, options: &Options) -> Result<Value> {
+    let mut map = IndexMap::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        let line = line.trim_start();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").map(str::trim_start).unwrap_or(line);
+        let Some((key, rest)) = line.split_once('=') else {
+            return Err(ParseError::new("expected KEY=value", n + 1, None).into());
+        };
+        let key = key.trim();
+        check_key(key).map_err(|err| ParseError::new(err.to_string(), n + 1, None))?;
+
+        let rest = rest.trim_start();
+        let value = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut text = rest[1..].to_string();
+                let end = loop {
+                    if let Some(end) = closing(&text, quote) {
+                        break end;
+                    }
+                    let Some((_, next)) = lines.next() else {
+                        return Err(ParseError::new(format!("quoted value of {key} isn't closed"), n + 1, None).into());
+                    };
+                    text.push('\n');
+                    text.push_str(next);
+                };
+                let after = text[end + 1..].trim();
+                if !after.is_empty() && !after.starts_with('#') {
+                    return Err(ParseError::new(format!("unexpected text after the quoted value of {key}"), n + 1, None).into());
+                }
+                text.truncate(end);
+                match quote {
+                    '"' => unescape(&text),
+                    _ => text,
+                }
+            }
+            _ => {
+                let end = rest.find(" #").unwrap_or(rest.len());
+                rest[..end].trim_end().to_string()
+            }
+        };
+        let value = match options.infer_types {
+            true => super::csv::infer(&value),
+            false => Value::String(value),
+        };
+        map.insert(key.to_string(), value);
+    }
+    Ok(Value::Object(map))
+}
+
+/// where the closing quote is, skipping escaped ones.
+fn closing(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quote == '"' => escaped = true,
+            c if c == quote => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some(c @ ('"' | '\\' | '$' | '`')) => out.push(c),
+            // anything else keeps its backslash
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+fn check_key(key: &str) -> Result<()> {
+    let valid = key.chars().next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.'));
+    if !valid {
+        return Err(anyhow!("{key:?} isn't a valid variable name"));
+    }
+    Ok(())
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    let Value::Object(map) = value else {
+        return Err(anyhow!(".env needs an object, not {}", value.type_name()));
+    };
+    let mut out = String::new();
+    for (key, value) in map {
+        let Some(text) = scalar_text(value) else {
+            let message = format!(".env only holds top-level keys with scalar values, {} is {}", pointer(&[key]), value.type_name());
+            return Err(ConvertError::new(message, pointer(&[key]))
+                .with_help("use properties to keep the nesting as dotted keys, or yaml")
+                .into());
+        };
+        check_key(key).map_err(|err| anyhow!("{err} at {}", pointer(&[key])))?;
+        writeln!(out, "{key}={}", quote(&text))?;
+    }
+    Ok(out)
+}
+
+/// double quotes values with anything a shell would
+/// treat specially.
+fn quote(text: &str) -> String {
+    let bare = text.chars().all(|c| c.is_ascii_alphanumeric() || "_-./:@,+%".contains(c));
+    if bare {
+        return text.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' | '$' | '`' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_env() {
+        let input = "# settings\nexport HOST=localhost # the host\nPORT = 8080\nRAW='a $b \\n'\nMULTI=\"line one\nline \\\"two\\\"\"\nEMPTY=\n";
+        let expected = json(r#"{"HOST": "localhost", "PORT": "8080", "RAW": "a $b \\n", "MULTI": "line one\nline \"two\"", "EMPTY": ""}"#);
+        assert_eq!(read(input, &Options::default()).unwrap(), expected);
+
+        assert_eq!(read("A=1\nB\n", &Options::default()).unwrap_err().to_string(), "expected KEY=value at line 2");
+        assert_eq!(read("1A=x\n", &Options::default()).unwrap_err().to_string(), "\"1A\" isn't a valid variable name at line 1");
+        assert_eq!(read("A=\"open\n", &Options::default()).unwrap_err().to_string(), "quoted value of A isn't closed at line 1");
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let value = json(r#"{"HOST": "db.local:5432", "PASS": "p$ss \"w\"\nx", "PORT": 8080, "NONE": null}"#);
+        let env = write(&value).unwrap();
+        assert_eq!(env, "HOST=db.local:5432\nPASS=\"p\\$ss \\\"w\\\"\\nx\"\nPORT=8080\nNONE=\n");
+        let options = Options { infer_types: true, ..Default::default() };
+        assert_eq!(read(&env, &options).unwrap(), value);
+    }
+
+    #[test]
+    fn write_errors() {
+        let err = write(&json(r#"{"db": {"host": "x"}}"#)).unwrap_err();
+        assert_eq!(err.to_string(), ".env only holds top-level keys with scalar values, /db is object");
+        assert!(write(&json(r#"{"a b": 1}"#)).is_err());
+    }
+}
@@ -0,0 +1,427 @@
+//! hjson (https://hjson.github.io): json for humans, with comments,
+//! optional commas and quotes, ''' multiline strings and braces
+//! around the root object left out. writing leaves out the commas
+//! and the quotes that aren't needed.
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use indexmap::IndexMap;
+
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+/// characters a quoteless value or key can't start with.
+const PUNCTUATORS: &[char] = &['{', '}', '[', ']', ',', ':'];
+
+pub fn read(input: &str) -> Result<Value> {
+    let mut parser = Parser { input, pos: 0 };
+    parser.skip_space();
+    let value = match parser.peek() {
+        Some('{' | '[') => parser.value()?,
+        // a root value that isn't an object
+        Some(_) if parser.rest().lines().count() == 1 && !parser.rest().contains(':') => parser.value()?,
+        _ => parser.members(None)?,
+    };
+    parser.skip_space();
+    if parser.peek().is_some() {
+        return Err(parser.error("unexpected text after the value"));
+    }
+    Ok(value)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    /// byte offset into input
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn error(&self, msg: &str) -> anyhow::Error {
+        ParseError::at_offset(msg, self.input, self.pos).into()
+    }
+
+    /// skips whitespace and comments.
+    fn skip_space(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') || trimmed.starts_with("//") {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else if let Some(comment) = trimmed.strip_prefix("/*") {
+                self.pos += comment.find("*/").map(|end| end + 4).unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.peek() {
+            Some('{') => {
+                self.bump();
+                self.members(Some('}'))
+            }
+            Some('[') => {
+                self.bump();
+                self.elements()
+            }
+            Some('"') => Ok(Value::String(self.quoted('"')?)),
+            Some('\'') if self.rest().starts_with("'''") => Ok(Value::String(self.multiline()?)),
+            Some('\'') => Ok(Value::String(self.quoted('\'')?)),
+            Some(c) if PUNCTUATORS.contains(&c) => Err(self.error(&format!("unexpected {c:?}"))),
+            Some(_) => Ok(self.quoteless()),
+            None => Err(self.error("expected a value")),
+        }
+    }
+
+    /// an object's members, up to close (or the
+    /// end of input for a braceless root).
+    fn members(&mut self, close: Option<char>) -> Result<Value> {
+        let mut map = IndexMap::new();
+        loop {
+            self.skip_space();
+            match (self.peek(), close) {
+                (None, None) => break,
+                (None, Some(close)) => return Err(self.error(&format!("expected {close:?}"))),
+                (Some(c), Some(close)) if c == close => {
+                    self.bump();
+                    break;
+                }
+                _ => {}
+            }
+            let key = self.key()?;
+            self.skip_space();
+            if self.peek() != Some(':') {
+                return Err(self.error(&format!("expected ':' after {key:?}")));
+            }
+            self.bump();
+            self.skip_space();
+            let value = self.value()?;
+            map.insert(key, value);
+            self.separator();
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn elements(&mut self) -> Result<Value> {
+        let mut items = Vec::new();
+        loop {
+            self.skip_space();
+            match self.peek() {
+                None => return Err(self.error("expected ']'")),
+                Some(']') => {
+                    self.bump();
+                    break;
+                }
+                _ => {}
+            }
+            items.push(self.value()?);
+            self.separator();
+        }
+        Ok(Value::Array(items))
+    }
+
+    /// commas between members and elements are optional.
+    fn separator(&mut self) {
+        self.skip_space();
+        if self.peek() == Some(',') {
+            self.bump();
+        }
+    }
+
+    fn key(&mut self) -> Result<String> {
+        match self.peek() {
+            Some(quote @ ('"' | '\'')) => self.quoted(quote),
+            _ => {
+                let end = self.rest()
+                    .find(|c: char| c.is_whitespace() || PUNCTUATORS.contains(&c))
+                    .unwrap_or(self.rest().len());
+                if end == 0 {
+                    return Err(self.error("expected a key"));
+                }
+                let key = self.rest()[..end].to_string();
+                self.pos += end;
+                Ok(key)
+            }
+        }
+    }
+
+    fn quoted(&mut self, quote: char) -> Result<String> {
+        self.bump();
+        let mut s = String::new();
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some(c) if c == quote => return Ok(s),
+                Some('\\') => {
+                    let c = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('u') => self.unicode_escape()?,
+                        Some(c @ ('"' | '\'' | '\\' | '/')) => c,
+                        _ => return Err(self.error("bad escape")),
+                    };
+                    s.push(c);
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    fn unicode_escape(&mut self) -> Result<char> {
+        let hex = |p: &mut Self| -> Result<u32> {
+            let digits = p.rest().get(..4).ok_or_else(|| p.error("bad \\u escape"))?;
+            let n = u32::from_str_radix(digits, 16).map_err(|_| p.error("bad \\u escape"))?;
+            p.pos += 4;
+            Ok(n)
+        };
+        let high = hex(self)?;
+        let code = if (0xD800..0xDC00).contains(&high) && self.rest().starts_with("\\u") {
+            self.pos += 2;
+            let low = hex(self)?;
+            0x10000 + ((high - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("bad \\u escape"))
+    }
+
+    /// '''
+    ///   lines, less the indentation of the opening quotes
+    ///   '''
+    fn multiline(&mut self) -> Result<String> {
+        let line_start = self.input[..self.pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let indent = self.input[line_start..self.pos].chars().count();
+        self.pos += 3;
+        let Some(end) = self.rest().find("'''") else {
+            return Err(self.error("unterminated ''' string"));
+        };
+        let body = &self.rest()[..end];
+        self.pos += end + 3;
+
+        let body = body.trim_start_matches([' ', '\t', '\r']);
+        let body = body.strip_prefix('\n').unwrap_or(body);
+        let lines: Vec<String> = body.split('\n')
+            .map(|line| {
+                let strip = line.chars().take(indent).take_while(|c| c.is_whitespace()).map(char::len_utf8).sum();
+                line[strip..].trim_end_matches('\r').to_string()
+            })
+            .collect();
+        let mut s = lines.join("\n");
+        // the line the closing quotes are on
+        if s.ends_with('\n') || lines.last().is_some_and(|l| l.trim().is_empty()) {
+            s.truncate(s.trim_end_matches([' ', '\t']).len());
+            if s.ends_with('\n') {
+                s.pop();
+            }
+        }
+        Ok(s)
+    }
+
+    /// a keyword or number, if it's all there is before a separator
+    /// or comment, otherwise a string running to the end of the line.
+    fn quoteless(&mut self) -> Value {
+        let line = self.rest().split('\n').next().unwrap_or_default();
+        let token_end = line.find([',', ']', '}', '#']).unwrap_or(line.len());
+        let token_end = [line.find("//"), line.find("/*"), Some(token_end)].into_iter().flatten().min().unwrap();
+        if let Some(value) = literal(line[..token_end].trim()) {
+            self.pos += token_end;
+            return value;
+        }
+        self.pos += line.len();
+        Value::String(line.trim().to_string())
+    }
+}
+
+/// a keyword or number.
+fn literal(token: &str) -> Option<Value> {
+    match token {
+        "true" => return Some(Value::Bool(true)),
+        "false" => return Some(Value::Bool(false)),
+        "null" => return Some(Value::Null),
+        _ => {}
+    }
+    // json's number grammar, so things like 1_000 or .5 stay strings
+    let digits = token.strip_prefix('-').unwrap_or(token);
+    let (int, frac_exp) = digits.split_at(digits.find(['.', 'e', 'E']).unwrap_or(digits.len()));
+    let int_ok = !int.is_empty() && int.bytes().all(|b| b.is_ascii_digit()) && (int == "0" || !int.starts_with('0'));
+    if !int_ok {
+        return None;
+    }
+    if frac_exp.is_empty() {
+        return token.parse().ok().map(Value::Integer);
+    }
+    let (frac, exp) = frac_exp.split_at(frac_exp.find(['e', 'E']).unwrap_or(frac_exp.len()));
+    let frac_ok = frac.is_empty() || frac.len() > 1 && frac[1..].bytes().all(|b| b.is_ascii_digit());
+    let exp_digits = exp.get(1..).map(|e| e.trim_start_matches(['+', '-']));
+    let exp_ok = exp.is_empty() || exp_digits.is_some_and(|e| !e.is_empty() && e.bytes().all(|b| b.is_ascii_digit()));
+    if !frac_ok || !exp_ok {
+        return None;
+    }
+    token.parse().ok().map(Value::Float)
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    super::json::check(value, &mut Vec::new())?;
+    let mut out = String::new();
+    write_value(value, 0, &mut out);
+    out.push('\n');
+    Ok(out)
+}
+
+fn write_value(value: &Value, indent: usize, out: &mut String) {
+    let pad = |n: usize| "  ".repeat(n);
+    match value {
+        Value::Object(map) if map.is_empty() => out.push_str("{}"),
+        Value::Array(items) if items.is_empty() => out.push_str("[]"),
+        Value::Object(map) => {
+            out.push_str("{\n");
+            for (key, value) in map {
+                out.push_str(&pad(indent + 1));
+                out.push_str(&key_text(key));
+                out.push_str(": ");
+                write_value(value, indent + 1, out);
+                out.push('\n');
+            }
+            out.push_str(&pad(indent));
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push_str("[\n");
+            for item in items {
+                out.push_str(&pad(indent + 1));
+                write_value(item, indent + 1, out);
+                out.push('\n');
+            }
+            out.push_str(&pad(indent));
+            out.push(']');
+        }
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(&b.to_string()),
+        Value::Integer(i) => out.push_str(&i.to_string()),
+        // {:?} keeps the .0 so it reads back as a float
+        Value::Float(f) => out.push_str(&format!("{f:?}")),
+        Value::String(s) => out.push_str(&string_text(s)),
+        Value::Bytes(b) => out.push_str(&string_text(&STANDARD.encode(b))),
+    }
+}
+
+fn key_text(key: &str) -> String {
+    let quoteless = !key.is_empty()
+        && !key.contains(|c: char| c.is_whitespace() || PUNCTUATORS.contains(&c) || matches!(c, '"' | '\'' | '#' | '/'));
+    match quoteless {
+        true => key.to_string(),
+        false => quote(key),
+    }
+}
+
+/// quoteless when it'd read back as the same string.
+fn string_text(s: &str) -> String {
+    let quoteless = !s.is_empty()
+        && s.trim() == s
+        && !s.contains(['\n', '\r'])
+        && !s.starts_with(PUNCTUATORS)
+        && !s.starts_with(['"', '\'', '#'])
+        && !s.starts_with("//")
+        && !s.starts_with("/*")
+        // e.g. "1, 2" would read back as 1
+        && Parser { input: s, pos: 0 }.quoteless() == Value::String(s.to_string());
+    match quoteless {
+        true => s.to_string(),
+        false => quote(s),
+    }
+}
+
+fn quote(s: &str) -> String {
+    serde_json::to_string(s).expect("strings always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_hjson() {
+        let input = r#"
+            # a comment
+            name: cff, the converter
+            version: 1 // a number
+            ratio: 0.5
+            url: http://example.com
+            "quoted key": 'single'
+            list: [1, 3.0e2
+              two
+              true]
+            nested: {a: null, b: {}}
+            text:
+              '''
+              first
+                indented
+              '''
+        "#;
+        let expected = json(r#"{
+            "name": "cff, the converter",
+            "version": 1,
+            "ratio": 0.5,
+            "url": "http://example.com",
+            "quoted key": "single",
+            "list": [1, 300.0, "two", true],
+            "nested": {"a": null, "b": {}},
+            "text": "first\n  indented"
+        }"#);
+        assert_eq!(read(input).unwrap(), expected);
+        assert_eq!(read("[1, 2]").unwrap(), json("[1, 2]"));
+        assert_eq!(read("1 2").unwrap(), json(r#""1 2""#), "a root string");
+    }
+
+    #[test]
+    fn literals() {
+        assert_eq!(literal("-1"), Some(Value::Integer(-1)));
+        assert_eq!(literal("1.5e3"), Some(Value::Float(1500.0)));
+        assert_eq!(literal("01"), None);
+        assert_eq!(literal("1."), None);
+        assert_eq!(literal("1e"), None);
+        assert_eq!(literal("truey"), None);
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let value = json(r#"{"a b": "x", "s": "1, 2", "t": " padded", "n": 1.0, "e": [], "l": ["true", true, "multi\nline"], "o": {"k": null}}"#);
+        let hjson = write(&value).unwrap();
+        assert!(hjson.contains("  \"a b\": x\n"), "{hjson}");
+        assert!(hjson.contains("  s: \"1, 2\"\n"), "{hjson}");
+        assert_eq!(read(&hjson).unwrap(), value);
+    }
+
+    #[test]
+    fn errors() {
+        let err = read("a: 1\nb: [1, 2\n").unwrap_err();
+        assert_eq!(err.to_string(), "expected ']' at line 3 column 1");
+        let err = read("{a 1}").unwrap_err();
+        assert_eq!(err.to_string(), "expected ':' after \"a\" at line 1 column 4");
+        assert!(read("a: \"open\n").is_err());
+    }
+}
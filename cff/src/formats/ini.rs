@@ -0,0 +1,213 @@
+//! sections are tables, with dots in their names for nesting
+//! (`[server.tls]`), and keys before the first section are top-level.
+//! values are strings unless --infer-types is set. quoted values can
+//! hold escapes (`\n`, `\"`), unquoted ones are taken as they are, up
+//! to a ` ;` or ` #` comment. ini has no arrays.
+
+use std::fmt::Write;
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+
+use super::{pointer, scalar_text, table, Options};
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str, options: &Options) -> Result<Value> {
+    let mut root = IndexMap::new();
+    let mut section: Vec<String> = Vec::new();
+    for (n, line) in input.lines().enumerate() {
+        let at = |message: String| ParseError::new(message, n + 1, None);
+        let line = line.trim();
+        if line.is_empty() || line.starts_with([';', '#']) {
+            continue;
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let name = name.strip_suffix(']')
+                .ok_or_else(|| at("section name isn't closed with ]".to_string()))?;
+            section = name.split('.').map(|part| part.trim().to_string()).collect();
+            table(&mut root, &section)
+                .ok_or_else(|| at(format!("[{name}] is already a value")))?;
+            continue;
+        }
+        let (key, value) = line.split_at(line.find(['=', ':']).ok_or_else(|| at("expected key = value".to_string()))?);
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(at("missing key".to_string()).into());
+        }
+        let value = value_text(&value[1..]).map_err(|err| at(err.to_string()))?;
+        let value = match options.infer_types {
+            true => super::csv::infer(&value),
+            false => Value::String(value),
+        };
+        let map = table(&mut root, &section)
+            .ok_or_else(|| at(format!("[{}] is already a value", section.join("."))))?;
+        if let Some(Value::Object(_)) = map.get(key) {
+            return Err(at(format!("{key} is already a section")).into());
+        }
+        map.insert(key.to_string(), value);
+    }
+    Ok(Value::Object(root))
+}
+
+/// the value after the =, unquoted and without comments.
+fn value_text(raw: &str) -> Result<String> {
+    let raw = raw.trim();
+    let Some(quoted) = raw.strip_prefix('"') else {
+        let end = [raw.find(" ;"), raw.find(" #"), raw.find("\t;"), raw.find("\t#")]
+            .into_iter()
+            .flatten()
+            .min()
+            .unwrap_or(raw.len());
+        return Ok(raw[..end].trim_end().to_string());
+    };
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => {
+                let rest = quoted[i + 1..].trim_start();
+                if !rest.is_empty() && !rest.starts_with([';', '#']) {
+                    return Err(anyhow!("unexpected text after quoted value"));
+                }
+                return Ok(value);
+            }
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => value.push('\n'),
+                Some('r') => value.push('\r'),
+                Some('t') => value.push('\t'),
+                Some(c) => value.push(c),
+                None => break,
+            },
+            c => value.push(c),
+        }
+    }
+    Err(anyhow!("quoted value isn't closed"))
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    let Value::Object(map) = value else {
+        return Err(anyhow!("ini needs an object of sections, not {}", value.type_name()));
+    };
+    let mut out = String::new();
+    // keys outside any section come first
+    write_keys(&mut out, map, &[])?;
+    write_sections(&mut out, map, &mut Vec::new())?;
+    Ok(out)
+}
+
+fn write_sections(out: &mut String, map: &IndexMap<String, Value>, path: &mut Vec<String>) -> Result<()> {
+    for (key, value) in map {
+        match value {
+            Value::Object(section) => {
+                path.push(key.clone());
+                if key.is_empty() || key.contains(['.', '[', ']', '\n']) {
+                    return Err(ConvertError::new(format!("{key:?} at {} can't be an ini section name", pointer(path)), pointer(path))
+                .with_help("section names can't be empty or have dots or brackets in them")
+                .into());
+                }
+                let has_keys = section.values().any(|v| !matches!(v, Value::Object(_)));
+                if has_keys || section.is_empty() {
+                    if !out.is_empty() {
+                        out.push('\n');
+                    }
+                    writeln!(out, "[{}]", path.join("."))?;
+                    write_keys(out, section, path)?;
+                }
+                write_sections(out, section, path)?;
+                path.pop();
+            }
+            Value::Array(_) => {
+                path.push(key.clone());
+                return Err(ConvertError::new(format!("ini can't hold arrays, like the one at {}", pointer(path)), pointer(path))
+                    .with_help("join the items into one string first, or use a format with arrays, like toml or yaml")
+                    .into());
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// the scalars of a section.
+fn write_keys(out: &mut String, map: &IndexMap<String, Value>, path: &[String]) -> Result<()> {
+    for (key, value) in map {
+        let Some(text) = scalar_text(value) else {
+            continue;
+        };
+        if key.is_empty() || key.trim() != key || key.contains(['=', ':', '\n']) || key.starts_with(['[', ';', '#']) {
+            let path = [path, std::slice::from_ref(key)].concat();
+            return Err(ConvertError::new(format!("{key:?} at {} can't be an ini key", pointer(&path)), pointer(&path))
+                .with_help("ini keys can't be empty, be padded with spaces or have =, : or newlines in them")
+                .into());
+        }
+        writeln!(out, "{key} = {}", quote(&text))?;
+    }
+    Ok(())
+}
+
+/// quotes values that wouldn't read back the same bare.
+fn quote(text: &str) -> String {
+    let bare = text.trim() == text && !text.starts_with('"') && !text.contains(['\n', '\r', ';', '#']);
+    if bare {
+        return text.to_string();
+    }
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_sections() {
+        let input = "; settings\nname = cff\n\n[server]\nhost: localhost ; the host\nport = 8080\n\n[server.tls]\ncert = \"a \\\"b\\\" #c\"\nempty =\n";
+        let expected = json(r#"{"name": "cff", "server": {"host": "localhost", "port": "8080", "tls": {"cert": "a \"b\" #c", "empty": ""}}}"#);
+        assert_eq!(read(input, &Options::default()).unwrap(), expected);
+
+        let options = Options { infer_types: true, ..Default::default() };
+        let expected = json(r#"{"name": "cff", "server": {"host": "localhost", "port": 8080, "tls": {"cert": "a \"b\" #c", "empty": null}}}"#);
+        assert_eq!(read(input, &options).unwrap(), expected);
+    }
+
+    #[test]
+    fn read_errors() {
+        assert_eq!(read("[a\n", &Options::default()).unwrap_err().to_string(), "section name isn't closed with ] at line 1");
+        assert_eq!(read("a = 1\nb\n", &Options::default()).unwrap_err().to_string(), "expected key = value at line 2");
+        assert_eq!(read("a = 1\n[a.b]\n", &Options::default()).unwrap_err().to_string(), "[a.b] is already a value at line 2");
+        assert!(read("x = \"open\n", &Options::default()).is_err());
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let value = json(r#"{"top": 1, "server": {"host": " padded ", "tls": {"on": true}, "note": "a;b"}, "empty": {}}"#);
+        let ini = write(&value).unwrap();
+        assert_eq!(ini, "top = 1\n\n[server]\nhost = \" padded \"\nnote = \"a;b\"\n\n[server.tls]\non = true\n\n[empty]\n");
+        let options = Options { infer_types: true, ..Default::default() };
+        assert_eq!(read(&ini, &options).unwrap(), value);
+    }
+
+    #[test]
+    fn write_errors() {
+        let err = write(&json(r#"{"server": {"hosts": ["a", "b"]}}"#)).unwrap_err();
+        assert_eq!(err.to_string(), "ini can't hold arrays, like the one at /server/hosts");
+        assert!(write(&json(r#"{"a.b": {"c": 1}}"#)).is_err(), "dotted section");
+        assert!(write(&json("[1]")).is_err());
+    }
+}
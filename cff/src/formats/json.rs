@@ -0,0 +1,62 @@
+use anyhow::Result;
+
+use super::pointer;
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value, ParseError> {
+    serde_json::from_str(input).map_err(|err| ParseError::from_serde(&err, err.line(), err.column()))
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    check(value, &mut Vec::new())?;
+    Ok(serde_json::to_string_pretty(value)?)
+}
+
+/// finds the infinities and NaNs serde_json would
+/// quietly write as null.
+pub fn check(value: &Value, path: &mut Vec<String>) -> Result<(), ConvertError> {
+    match value {
+        Value::Float(f) if !f.is_finite() => {
+            let name = match f.is_nan() {
+                true => "NaN",
+                false if *f > 0.0 => "Infinity",
+                false => "-Infinity",
+            };
+            Err(ConvertError::new(format!("json has no {name}, like the one at {}", pointer(path)), pointer(path))
+                .with_help("make it a string or null, or convert to yaml, which has .inf and .nan"))
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.to_string());
+                check(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                path.push(key.clone());
+                check(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_non_finite_floats() {
+        let value = Value::Object([("a".to_string(), Value::Array(vec![Value::Float(1.5), Value::Float(f64::NAN)]))].into_iter().collect());
+        let err = write(&value).unwrap_err().downcast::<ConvertError>().unwrap();
+        assert_eq!((err.message.as_str(), err.pointer.as_str()), ("json has no NaN, like the one at /a/1", "/a/1"));
+        assert!(err.help.is_some());
+        let err = write(&Value::Float(f64::INFINITY)).unwrap_err();
+        assert!(err.to_string().contains("json has no Infinity"), "{err}");
+    }
+}
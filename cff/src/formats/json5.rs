@@ -0,0 +1,32 @@
+use anyhow::{anyhow, Result};
+
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value> {
+    ::json5::from_str(input).map_err(|err| {
+        let ::json5::Error::Message { msg, location } = err;
+        // the message repeats the position with a snippet,
+        // the last line is what went wrong
+        let msg = msg.lines().last().unwrap_or_default().trim_start_matches([' ', '=']).to_string();
+        match location {
+            Some(at) => ParseError::new(msg, at.line, Some(at.column)).into(),
+            None => anyhow!(msg),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lenient_syntax() {
+        let input = "// config\n{unquoted: 'single', hex: 0x10, trailing: [1, 2,], plus: +1, half: .5,}";
+        let expected: Value = serde_json::from_str(r#"{"unquoted": "single", "hex": 16, "trailing": [1, 2], "plus": 1, "half": 0.5}"#).unwrap();
+        assert_eq!(read(input).unwrap(), expected);
+
+        let err = read("{a: 1,\n b: }").unwrap_err();
+        assert_eq!(err.to_string(), "expected array, boolean, null, number, object, or string at line 2 column 5");
+    }
+}
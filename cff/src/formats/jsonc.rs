@@ -0,0 +1,105 @@
+//! json with comments and trailing commas, as in tsconfig.json or
+//! vscode's settings.json. they're blanked out rather than removed
+//! so errors from the json parser point at the right line and column.
+
+use anyhow::Result;
+
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value> {
+    serde_json::from_str(&strip(input))
+        .map_err(|err| ParseError::from_serde(&err, err.line(), err.column()).into())
+}
+
+/// replaces comments and trailing commas with spaces,
+/// keeping newlines (and so positions) as they were.
+fn strip(input: &str) -> String {
+    let mut out = input.as_bytes().to_vec();
+    let mut i = 0;
+    let mut in_string = false;
+    // a comma that'd be trailing if a } or ] comes next
+    let mut comma = None;
+    // the last byte that isn't whitespace or a comment
+    let mut last = 0;
+    while i < out.len() {
+        let c = out[i];
+        if in_string {
+            match c {
+                b'\\' => i += 1,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            i += 1;
+            continue;
+        }
+        match (c, out.get(i + 1)) {
+            (b'/', Some(b'/')) => {
+                while i < out.len() && out[i] != b'\n' {
+                    out[i] = b' ';
+                    i += 1;
+                }
+                continue;
+            }
+            (b'/', Some(b'*')) => {
+                let end = out[i + 2..].windows(2).position(|w| w == b"*/").map(|p| i + 2 + p + 2);
+                // an unclosed comment runs to the end
+                let end = end.unwrap_or(out.len());
+                for b in &mut out[i..end] {
+                    if *b != b'\n' {
+                        *b = b' ';
+                    }
+                }
+                i = end;
+                continue;
+            }
+            (b'"', _) => {
+                in_string = true;
+                comma = None;
+            }
+            // with no value before it, the comma is left for
+            // the json parser to report where it is
+            (b',', _) => comma = (!matches!(last, b'{' | b'[' | b':' | b',')).then_some(i),
+            (b'}' | b']', _) => {
+                if let Some(comma) = comma.take() {
+                    out[comma] = b' ';
+                }
+            }
+            (c, _) if c.is_ascii_whitespace() => {}
+            _ => comma = None,
+        }
+        if !c.is_ascii_whitespace() {
+            last = c;
+        }
+        i += 1;
+    }
+    // only ascii bytes outside strings were replaced
+    String::from_utf8(out).expect("still utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn comments_and_trailing_commas() {
+        let input = r#"{
+            // compiler options
+            "a": [1, 2, /* two */ ],
+            "b": "// not a comment, /* nor this */", #
+        }"#;
+        assert!(read(input).is_err(), "# isn't a comment");
+        let input = input.replace('#', "");
+        let expected: Value = serde_json::from_str(r#"{"a": [1, 2], "b": "// not a comment, /* nor this */"}"#).unwrap();
+        assert_eq!(read(&input).unwrap(), expected);
+        assert_eq!(strip("[1, \"\\\",\", ]"), "[1, \"\\\",\"  ]");
+    }
+
+    #[test]
+    fn errors_keep_positions() {
+        let err = read("{\n  /* a\n  comment */ \"a\": }").unwrap_err();
+        assert!(err.to_string().contains("line 3 column 19"), "{err}");
+        let err = read("{\n  \"a\": 1,\n  \"b\": ,\n}").unwrap_err();
+        assert!(err.to_string().contains("line 3 column 8"), "a comma with no value isn't trailing: {err}");
+    }
+}
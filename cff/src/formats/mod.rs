@@ -0,0 +1,412 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use indexmap::IndexMap;
+
+use crate::Value;
+
+mod binary;
+mod csv;
+mod dotenv;
+mod hjson;
+mod ini;
+mod json;
+mod json5;
+mod jsonc;
+mod ndjson;
+mod properties;
+mod ron;
+pub mod stream;
+mod toml;
+mod xml;
+mod yaml;
+
+/// settings for the formats that need them.
+#[derive(Debug, Default, Clone, clap::Args)]
+pub struct Options {
+    /// xml element(s) to always read as arrays, even
+    /// when they only appear once. use * for all.
+    #[clap(long = "xml-array", global = true)]
+    pub xml_arrays: Vec<String>,
+
+    /// root element to wrap data in when writing xml,
+    /// if it doesn't have a single top-level key.
+    /// defaults to root.
+    #[clap(long, global = true)]
+    pub xml_root: Option<String>,
+
+    /// csv/tsv field delimiter, if not , or tab
+    #[clap(long, global = true)]
+    pub delimiter: Option<char>,
+
+    /// csv/tsv has no header line. rows are read
+    /// as arrays and written without a header.
+    #[clap(long, global = true)]
+    pub no_header: bool,
+
+    /// read csv/tsv cells and ini, .env and
+    /// properties values as numbers, bools and
+    /// nulls (empty ones) where they look like
+    /// them, rather than all as strings.
+    #[clap(long, global = true)]
+    pub infer_types: bool,
+
+    /// write a top-level array as a stream of
+    /// yaml documents rather than a list.
+    #[clap(long, global = true)]
+    pub yaml_documents: bool,
+
+    /// sort object keys. toml and yaml written as
+    /// themselves keep each key's comments with it.
+    #[clap(long, global = true)]
+    pub sort_keys: bool,
+
+    /// read and write binary formats (msgpack,
+    /// cbor, bson) as hex text.
+    #[clap(long, conflicts_with = "base64", global = true)]
+    pub hex: bool,
+
+    /// read and write binary formats (msgpack,
+    /// cbor, bson) as base64 text.
+    #[clap(long, global = true)]
+    pub base64: bool,
+}
+
+/// a supported file format. each one has a reader into
+/// and a writer from [`Value`], so adding a format here
+/// makes it convertible to and from every other one.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, clap::ValueEnum)]
+pub enum Format {
+    Json,
+    /// one json value per line
+    #[value(alias = "jsonl")]
+    Ndjson,
+    /// json with comments and trailing commas.
+    /// written as plain json.
+    Jsonc,
+    /// written as plain json.
+    Json5,
+    Hjson,
+    Ron,
+    #[value(alias = "yml")]
+    Yaml,
+    Toml,
+    Xml,
+    Csv,
+    Tsv,
+    Ini,
+    #[value(alias = "env")]
+    Dotenv,
+    Properties,
+    #[value(alias = "messagepack")]
+    Msgpack,
+    Cbor,
+    Bson,
+}
+
+impl Format {
+    /// the letter used in conversion codes like JY.
+    fn from_code(code: char) -> Option<Format> {
+        match code.to_ascii_uppercase() {
+            'J' => Some(Format::Json),
+            'Y' => Some(Format::Yaml),
+            'T' => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    /// the format a file extension implies, if any.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        // .env, .env.local and the like
+        let name = path.file_name()?.to_str()?;
+        if name == ".env" || name.starts_with(".env.") {
+            return Some(Format::Dotenv);
+        }
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "json" => Some(Format::Json),
+            "ndjson" | "jsonl" => Some(Format::Ndjson),
+            "jsonc" => Some(Format::Jsonc),
+            "json5" => Some(Format::Json5),
+            "hjson" => Some(Format::Hjson),
+            "ron" => Some(Format::Ron),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            "xml" => Some(Format::Xml),
+            "csv" => Some(Format::Csv),
+            "tsv" | "tab" => Some(Format::Tsv),
+            "ini" => Some(Format::Ini),
+            "env" => Some(Format::Dotenv),
+            "properties" => Some(Format::Properties),
+            "msgpack" | "mpk" | "mp" => Some(Format::Msgpack),
+            "cbor" => Some(Format::Cbor),
+            "bson" => Some(Format::Bson),
+            _ => None,
+        }
+    }
+
+    /// guesses the format from the content itself. json is
+    /// also valid yaml, so json wins; otherwise exactly one
+    /// format has to parse it into an object or array.
+    /// of the binary formats only bson can be told apart.
+    pub fn sniff(input: &[u8]) -> Result<Format> {
+        if binary::bson::looks_like(input) {
+            return Ok(Format::Bson);
+        }
+        let Ok(input) = std::str::from_utf8(input) else {
+            return Err(anyhow!("input is binary, use --from msgpack, cbor or bson"));
+        };
+        if input.trim().is_empty() {
+            return Err(anyhow!("can't tell the format of empty input, use --from"));
+        }
+        if json::read(input).is_ok() {
+            return Ok(Format::Json);
+        }
+        if input.trim().lines().count() > 1 && ndjson::read(input).is_ok() {
+            return Ok(Format::Ndjson);
+        }
+        if input.trim_start().starts_with('<') {
+            return Ok(Format::Xml);
+        }
+        // yaml reads some of these, but not the way they're meant
+        if input.trim_start().starts_with(['{', '[']) {
+            if jsonc::read(input).is_ok() {
+                return Ok(Format::Jsonc);
+            }
+            if json5::read(input).is_ok() {
+                return Ok(Format::Json5);
+            }
+        }
+        if input.trim_start().starts_with('(') && ron::read(input).is_ok() {
+            return Ok(Format::Ron);
+        }
+        let candidates: Vec<Format> = [Format::Yaml, Format::Toml]
+            .into_iter()
+            .filter(|f| matches!(f.read(input.as_bytes(), &Options::default()), Ok(Value::Object(_) | Value::Array(_))))
+            .collect();
+        match candidates[..] {
+            [format] => Ok(format),
+            [] => sniff_delimited(input)
+                .ok_or_else(|| anyhow!("can't tell the format of the input, use --from")),
+            _ => Err(anyhow!("input could be any of {}, use --from", names(&candidates))),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Ndjson => "ndjson",
+            Format::Jsonc => "jsonc",
+            Format::Json5 => "json5",
+            Format::Hjson => "hjson",
+            Format::Ron => "ron",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+            Format::Xml => "xml",
+            Format::Csv => "csv",
+            Format::Tsv => "tsv",
+            Format::Ini => "ini",
+            Format::Dotenv => "dotenv",
+            Format::Properties => "properties",
+            Format::Msgpack => "msgpack",
+            Format::Cbor => "cbor",
+            Format::Bson => "bson",
+        }
+    }
+
+    pub fn is_binary(self) -> bool {
+        matches!(self, Format::Msgpack | Format::Cbor | Format::Bson)
+    }
+
+    pub fn read(self, input: &[u8], options: &Options) -> Result<Value> {
+        let mut value = self.read_value(input, options)?;
+        if options.sort_keys {
+            value.sort_keys();
+        }
+        Ok(value)
+    }
+
+    /// each document of a yaml stream on its own. anything
+    /// else is the one document.
+    pub fn documents(self, input: &[u8], options: &Options) -> Result<Vec<Value>> {
+        if self != Format::Yaml {
+            return Ok(vec![self.read(input, options)?]);
+        }
+        let input = std::str::from_utf8(input).context("yaml input isn't valid utf-8")?;
+        let mut docs = yaml::documents(input)?;
+        if options.sort_keys {
+            docs.iter_mut().for_each(Value::sort_keys);
+        }
+        Ok(docs)
+    }
+
+    fn read_value(self, input: &[u8], options: &Options) -> Result<Value> {
+        if self.is_binary() {
+            let input = binary::decode(input, options)?;
+            return match self {
+                Format::Msgpack => binary::msgpack::read(&input),
+                Format::Cbor => binary::cbor::read(&input),
+                _ => binary::bson::read(&input),
+            };
+        }
+        let input = std::str::from_utf8(input)
+            .with_context(|| format!("{} input isn't valid utf-8", self.name()))?;
+        match self {
+            Format::Json => json::read(input).map_err(|err| match jsonc::read(input) {
+                Ok(_) => err.with_help("this looks like json with comments or trailing commas, use --from jsonc").into(),
+                Err(_) => err.into(),
+            }),
+            Format::Ndjson => ndjson::read(input),
+            Format::Jsonc => jsonc::read(input),
+            Format::Json5 => json5::read(input),
+            Format::Hjson => hjson::read(input),
+            Format::Ron => ron::read(input),
+            Format::Yaml => yaml::read(input),
+            Format::Toml => toml::read(input),
+            Format::Xml => xml::read(input, options),
+            Format::Csv => csv::read(input, b',', options),
+            Format::Tsv => csv::read(input, b'\t', options),
+            Format::Ini => ini::read(input, options),
+            Format::Dotenv => dotenv::read(input, options),
+            Format::Properties => properties::read(input, options),
+            Format::Msgpack | Format::Cbor | Format::Bson => unreachable!("handled above"),
+        }
+    }
+
+    pub fn write(self, value: &Value, options: &Options) -> Result<Vec<u8>> {
+        let text = match self {
+            Format::Json | Format::Jsonc | Format::Json5 => json::write(value)?,
+            Format::Ndjson => ndjson::write(value)?,
+            Format::Hjson => hjson::write(value)?,
+            Format::Ron => ron::write(value)?,
+            Format::Yaml => yaml::write(value, options)?,
+            Format::Toml => toml::write(value)?,
+            Format::Xml => xml::write(value, options)?,
+            Format::Csv => csv::write(value, b',', options)?,
+            Format::Tsv => csv::write(value, b'\t', options)?,
+            Format::Ini => ini::write(value)?,
+            Format::Dotenv => dotenv::write(value)?,
+            Format::Properties => properties::write(value)?,
+            Format::Msgpack => return Ok(binary::encode(binary::msgpack::write(value)?, options)),
+            Format::Cbor => return Ok(binary::encode(binary::cbor::write(value)?, options)),
+            Format::Bson => return Ok(binary::encode(binary::bson::write(value)?, options)),
+        };
+        Ok(text.into_bytes())
+    }
+}
+
+/// tsv or csv if the first few rows parse with the
+/// same number (more than one) of fields.
+fn sniff_delimited(input: &str) -> Option<Format> {
+    [(Format::Tsv, b'\t'), (Format::Csv, b',')]
+        .into_iter()
+        .find(|(_, delimiter)| {
+            let mut reader = ::csv::ReaderBuilder::new()
+                .delimiter(*delimiter)
+                .has_headers(false)
+                .from_reader(input.as_bytes());
+            let rows: Result<Vec<_>, _> = reader.records().take(10).collect();
+            rows.is_ok_and(|rows| rows.len() > 1 && rows[0].len() > 1)
+        })
+        .map(|(format, _)| format)
+}
+
+/// a scalar as text, for formats where everything is text.
+fn scalar_text(value: &Value) -> Option<String> {
+    match value {
+        Value::Null => Some(String::new()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::String(s) => Some(s.clone()),
+        Value::Bytes(b) => Some(STANDARD.encode(b)),
+        Value::Array(_) | Value::Object(_) => None,
+    }
+}
+
+/// the json pointer to a value, e.g. /servers/0/name.
+pub(crate) fn pointer<S: AsRef<str>>(path: &[S]) -> String {
+    path.iter()
+        .map(|key| format!("/{}", key.as_ref().replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// the object at path, made if it's not there. none if
+/// something other than an object is in the way.
+fn table<'m>(root: &'m mut IndexMap<String, Value>, path: &[String]) -> Option<&'m mut IndexMap<String, Value>> {
+    let mut map = root;
+    for key in path {
+        let entry = map.entry(key.clone()).or_insert_with(|| Value::Object(IndexMap::new()));
+        let Value::Object(next) = entry else {
+            return None;
+        };
+        map = next;
+    }
+    Some(map)
+}
+
+fn names(formats: &[Format]) -> String {
+    formats.iter().map(|f| f.name()).collect::<Vec<_>>().join(", ")
+}
+
+/// parses a two letter conversion code, e.g. JY
+/// for json to yaml.
+pub fn parse_conversion(conversion: &str) -> Result<(Format, Format)> {
+    let mut codes = conversion.chars().map(Format::from_code);
+    match (codes.next(), codes.next(), codes.next()) {
+        (Some(Some(from)), Some(Some(to)), None) if from != to => Ok((from, to)),
+        _ => Err(anyhow!("conversion unsupported")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_conversion_codes() {
+        assert_eq!(parse_conversion("JY").unwrap(), (Format::Json, Format::Yaml));
+        assert_eq!(parse_conversion("ty").unwrap(), (Format::Toml, Format::Yaml));
+        assert!(parse_conversion("JJ").is_err());
+        assert!(parse_conversion("JX").is_err());
+        assert!(parse_conversion("JYT").is_err());
+    }
+
+    #[test]
+    fn format_from_path() {
+        assert_eq!(Format::from_path(Path::new("Cargo.toml")), Some(Format::Toml));
+        assert_eq!(Format::from_path(Path::new("dir/config.YML")), Some(Format::Yaml));
+        assert_eq!(Format::from_path(Path::new("a.json")), Some(Format::Json));
+        assert_eq!(Format::from_path(Path::new("settings.jsonc")), Some(Format::Jsonc));
+        assert_eq!(Format::from_path(Path::new("level.ron")), Some(Format::Ron));
+        assert_eq!(Format::from_path(Path::new("app/.env")), Some(Format::Dotenv));
+        assert_eq!(Format::from_path(Path::new(".env.local")), Some(Format::Dotenv));
+        assert_eq!(Format::from_path(Path::new("app.properties")), Some(Format::Properties));
+        assert_eq!(Format::from_path(Path::new("notes.txt")), None);
+        assert_eq!(Format::from_path(Path::new("Makefile")), None);
+    }
+
+    #[test]
+    fn sniff_formats() {
+        assert_eq!(Format::sniff(br#"{"a": [1, 2]}"#).unwrap(), Format::Json);
+        assert_eq!(Format::sniff(b"[1, 2]").unwrap(), Format::Json, "json before yaml");
+        assert_eq!(Format::sniff(b"{\"a\": 1}\n{\"a\": 2}\n").unwrap(), Format::Ndjson);
+        assert_eq!(Format::sniff(b"<?xml version=\"1.0\"?><a/>").unwrap(), Format::Xml);
+        assert_eq!(Format::sniff(b"a: 1\nb: [x]\n").unwrap(), Format::Yaml);
+        assert_eq!(Format::sniff(b"[package]\nname = \"cff\"\n").unwrap(), Format::Toml);
+        assert_eq!(Format::sniff(b"a = 1\n").unwrap(), Format::Toml, "a yaml string, not an object");
+        assert!(Format::sniff(b"[a]\n").is_err(), "a yaml list or a toml table");
+        assert_eq!(Format::sniff(b"{\"a\": 1, // one\n}").unwrap(), Format::Jsonc);
+        assert_eq!(Format::sniff(b"{a: 'x'}").unwrap(), Format::Json5);
+        assert_eq!(Format::sniff(b"(a: 1, b: [2])").unwrap(), Format::Ron);
+        assert_eq!(Format::sniff(b"a,b\n1,2\n").unwrap(), Format::Csv);
+        assert_eq!(Format::sniff(b"a,b\n\"1,5\",2\n").unwrap(), Format::Csv, "quoted delimiter");
+        assert_eq!(Format::sniff(b"a\tb,c\n1\t2\n").unwrap(), Format::Tsv);
+        assert!(Format::sniff(b"just some words").is_err());
+        assert_eq!(Format::sniff(b"\x0c\0\0\0\x10a\0\x01\0\0\0\0").unwrap(), Format::Bson);
+        assert!(Format::sniff(b"\x81\xa1a\x01").is_err(), "msgpack can't be sniffed");
+        assert!(Format::sniff(b"  \n").is_err());
+    }
+}
@@ -0,0 +1,63 @@
+//! one json value per line. read as an array of the values,
+//! and an array is written one item per line.
+
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value> {
+    Ok(Value::Array(records(input.as_bytes()).collect::<Result<_>>()?))
+}
+
+/// the values one at a time, skipping blank lines.
+pub fn records(input: impl BufRead) -> impl Iterator<Item = Result<Value>> {
+    input.lines().enumerate().filter_map(|(n, line)| match line {
+        Err(err) => Some(Err(err.into())),
+        Ok(line) if line.trim().is_empty() => None,
+        // serde_json only knows about the one line
+        Ok(line) => Some(serde_json::from_str(&line).map_err(|err| ParseError::from_serde(&err, n + 1, err.column()).into())),
+    })
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    let mut out = Vec::new();
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                write_record(&mut out, item)?;
+            }
+        }
+        other => write_record(&mut out, other)?,
+    }
+    Ok(String::from_utf8(out)?)
+}
+
+pub fn write_record(mut out: impl Write, value: &Value) -> Result<()> {
+    super::json::check(value, &mut Vec::new())?;
+    serde_json::to_writer(&mut out, value)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_and_write() {
+        let value = read("{\"a\": 1}\n\n[2, 3]\n\"x\"\n").unwrap();
+        assert_eq!(value, json(r#"[{"a": 1}, [2, 3], "x"]"#));
+        assert_eq!(write(&value).unwrap(), "{\"a\":1}\n[2,3]\n\"x\"\n");
+        assert_eq!(write(&json(r#"{"a": 1}"#)).unwrap(), "{\"a\":1}\n");
+
+        let err = read("{\"a\": 1}\n{\"a\": }\n").unwrap_err();
+        assert_eq!(err.to_string(), "expected value at line 2 column 7");
+    }
+}
@@ -0,0 +1,220 @@
+//! java .properties: `key=value`, `key: value` or `key value`, with
+//! `#`/`!` comments, backslash escapes and lines continued with a
+//! trailing backslash. dots in keys nest, so `db.host=x` reads as
+//! {"db": {"host": "x"}} and writes back the same way. there are no
+//! arrays.
+
+use std::fmt::Write;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+
+use super::{pointer, scalar_text, table, Options};
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str, options: &Options) -> Result<Value> {
+    let mut root = IndexMap::new();
+    let mut lines = input.lines().enumerate();
+    while let Some((n, line)) = lines.next() {
+        let mut line = line.trim_start().to_string();
+        if line.is_empty() || line.starts_with(['#', '!']) {
+            continue;
+        }
+        while continues(&line) {
+            line.pop();
+            match lines.next() {
+                Some((_, next)) => line.push_str(next.trim_start()),
+                None => break,
+            }
+        }
+
+        let (key, value) = split(&line);
+        let value = match options.infer_types {
+            true => super::csv::infer(&value),
+            false => Value::String(value),
+        };
+        let mut path: Vec<String> = key.split('.').map(str::to_string).collect();
+        let last = path.pop().unwrap_or_default();
+        let conflict = || ParseError::new(format!("{key} is both a value and a table"), n + 1, None);
+        let map = table(&mut root, &path).ok_or_else(conflict)?;
+        if let Some(Value::Object(_)) = map.get(&last) {
+            return Err(conflict().into());
+        }
+        map.insert(last, value);
+    }
+    Ok(Value::Object(root))
+}
+
+/// an odd number of trailing backslashes continues the line.
+fn continues(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// the unescaped key and value of a logical line.
+fn split(line: &str) -> (String, String) {
+    let mut chars = line.chars().peekable();
+    let mut key = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescape(chars.next(), &mut chars, &mut key),
+            '=' | ':' => break,
+            c if c.is_whitespace() => {
+                while chars.next_if(|c| c.is_whitespace()).is_some() {}
+                // whitespace can be followed by the separator
+                chars.next_if(|c| matches!(c, '=' | ':'));
+                break;
+            }
+            c => key.push(c),
+        }
+    }
+    while chars.next_if(|c| c.is_whitespace()).is_some() {}
+    let mut value = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescape(chars.next(), &mut chars, &mut value),
+            c => value.push(c),
+        }
+    }
+    (key, value)
+}
+
+fn unescape(c: Option<char>, chars: &mut Peekable<Chars>, out: &mut String) {
+    match c {
+        Some('t') => out.push('\t'),
+        Some('n') => out.push('\n'),
+        Some('r') => out.push('\r'),
+        Some('f') => out.push('\u{c}'),
+        Some('u') => {
+            let hex: String = chars.take(4).collect();
+            let mut code = u32::from_str_radix(&hex, 16).ok();
+            // characters past \uFFFF come as a surrogate pair
+            if let Some(high @ 0xD800..=0xDBFF) = code {
+                let mut ahead = chars.clone();
+                if ahead.next() == Some('\\') && ahead.next() == Some('u') {
+                    let low: String = ahead.by_ref().take(4).collect();
+                    if let Ok(low @ 0xDC00..=0xDFFF) = u32::from_str_radix(&low, 16) {
+                        code = Some(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00));
+                        *chars = ahead;
+                    }
+                }
+            }
+            match code.and_then(char::from_u32) {
+                Some(c) => out.push(c),
+                None => out.push_str(&hex),
+            }
+        }
+        Some(c) => out.push(c),
+        None => {}
+    }
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    let Value::Object(map) = value else {
+        return Err(anyhow!("properties needs an object, not {}", value.type_name()));
+    };
+    let mut out = String::new();
+    write_keys(&mut out, map, &mut Vec::new())?;
+    Ok(out)
+}
+
+fn write_keys(out: &mut String, map: &IndexMap<String, Value>, path: &mut Vec<String>) -> Result<()> {
+    for (key, value) in map {
+        path.push(key.clone());
+        if key.contains('.') {
+            return Err(ConvertError::new(format!("{key:?} at {} can't be a properties key", pointer(path)), pointer(path))
+                .with_help("dots in keys nest when read back, so rename the key or nest it yourself")
+                .into());
+        }
+        match value {
+            Value::Object(map) => write_keys(out, map, path)?,
+            Value::Array(_) => {
+                return Err(ConvertError::new(format!("properties can't hold arrays, like the one at {}", pointer(path)), pointer(path))
+                    .with_help("join the items into one string first, or use a format with arrays, like toml or yaml")
+                    .into());
+            }
+            scalar => {
+                let text = scalar_text(scalar).unwrap_or_default();
+                let key: Vec<String> = path.iter().map(|k| escape(k, true)).collect();
+                writeln!(out, "{}={}", key.join("."), escape(&text, false))?;
+            }
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+fn escape(text: &str, key: bool) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for (i, c) in text.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '\u{c}' => escaped.push_str("\\f"),
+            '=' | ':' | '#' | '!' if key => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            // leading spaces in values would be skipped
+            ' ' if key || i == 0 => escaped.push_str("\\ "),
+            // properties files are read as latin-1 by java
+            c if !c.is_ascii() => {
+                for unit in c.encode_utf16(&mut [0; 2]) {
+                    write!(escaped, "\\u{unit:04X}").expect("writing to a string");
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_properties() {
+        let input = "# app\n! settings\ndb.host = localhost\ndb.port:5432\nname cff\nlong = one \\\n    two\nkey\\ with\\=sep = \\u00e9\\tx\nempty\n";
+        let expected = json(r#"{
+            "db": {"host": "localhost", "port": "5432"},
+            "name": "cff",
+            "long": "one two",
+            "key with=sep": "é\tx",
+            "empty": ""
+        }"#);
+        assert_eq!(read(input, &Options::default()).unwrap(), expected);
+
+        let err = read("a=1\na.b=2\n", &Options::default()).unwrap_err();
+        assert_eq!(err.to_string(), "a.b is both a value and a table at line 2");
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let value = json(r#"{"db": {"host": "localhost", "port": 5432}, "key: x": " padded", "multi": "a\nb"}"#);
+        let properties = write(&value).unwrap();
+        assert_eq!(properties, "db.host=localhost\ndb.port=5432\nkey\\:\\ x=\\ padded\nmulti=a\\nb\n");
+        let options = Options { infer_types: true, ..Default::default() };
+        assert_eq!(read(&properties, &options).unwrap(), value);
+
+        let value = json(r#"{"café": "é 😀"}"#);
+        let properties = write(&value).unwrap();
+        assert_eq!(properties, "caf\\u00E9=\\u00E9 \\uD83D\\uDE00\n", "non-ascii as \\u escapes");
+        assert_eq!(read(&properties, &Options::default()).unwrap(), value);
+
+        let err = write(&json(r#"{"db": {"x.y": 1}}"#)).unwrap_err();
+        let err = err.downcast::<ConvertError>().unwrap();
+        assert_eq!((err.message.as_str(), err.pointer.as_str()), (r#""x.y" at /db/x.y can't be a properties key"#, "/db/x.y"));
+
+        let err = write(&json(r#"{"db": {"hosts": ["a"]}}"#)).unwrap_err();
+        assert_eq!(err.to_string(), "properties can't hold arrays, like the one at /db/hosts");
+    }
+}
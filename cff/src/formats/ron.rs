@@ -0,0 +1,36 @@
+//! structs and maps both read as objects and tuples as arrays.
+//! enum variants lose their names: unit variants read as null,
+//! the others as their contents.
+
+use anyhow::Result;
+use ::ron::ser::PrettyConfig;
+
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value> {
+    ::ron::from_str(input).map_err(|err| {
+        let at = err.span.start;
+        ParseError::new(err.code.to_string(), at.line, Some(at.col)).into()
+    })
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    Ok(::ron::ser::to_string_pretty(value, PrettyConfig::default())?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_and_write() {
+        let value = read(r#"Config(name: "cff", sizes: (1, 2), extra: Some({"a": true}), nothing: None)"#).unwrap();
+        let expected: Value = serde_json::from_str(r#"{"name": "cff", "sizes": [1, 2], "extra": {"a": true}, "nothing": null}"#).unwrap();
+        assert_eq!(value, expected);
+        assert_eq!(read(&write(&value).unwrap()).unwrap(), value);
+
+        let err = read("(a: 1,\n b: )").unwrap_err();
+        assert_eq!(err.to_string(), "Unexpected char ')' at line 2 column 4");
+    }
+}
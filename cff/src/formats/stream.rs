@@ -0,0 +1,91 @@
+//! converting record by record rather than reading all the input
+//! first, so exports bigger than memory can be converted. only the
+//! formats made of records can be streamed: ndjson, csv/tsv rows
+//! and the documents of a yaml stream.
+
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+
+use super::{csv, ndjson, yaml, Format, Options};
+use crate::Value;
+
+const STREAMABLE: &str = "ndjson, csv, tsv or yaml";
+
+pub fn convert(input: impl BufRead, output: impl Write, from: Format, to: Format, options: &Options) -> Result<()> {
+    let records: Box<dyn Iterator<Item = Result<Value>>> = match from {
+        Format::Ndjson => Box::new(ndjson::records(input)),
+        Format::Csv => Box::new(csv::records(input, b',', options)?),
+        Format::Tsv => Box::new(csv::records(input, b'\t', options)?),
+        Format::Yaml => Box::new(yaml::Documents::new(input)),
+        other => return Err(anyhow!("{} can't be streamed, only {STREAMABLE}", other.name())),
+    };
+    let mut writer = match to {
+        Format::Ndjson => Writer::Ndjson(output),
+        Format::Yaml => Writer::Yaml(output),
+        Format::Csv => Writer::Csv(Box::new(csv::RecordWriter::new(output, b',', options)?)),
+        Format::Tsv => Writer::Csv(Box::new(csv::RecordWriter::new(output, b'\t', options)?)),
+        other => return Err(anyhow!("{} can't be streamed, only {STREAMABLE}", other.name())),
+    };
+    for record in records {
+        let mut record = record?;
+        if options.sort_keys {
+            record.sort_keys();
+        }
+        writer.write(&record)?;
+    }
+    writer.finish()
+}
+
+enum Writer<W: Write> {
+    Ndjson(W),
+    Yaml(W),
+    Csv(Box<csv::RecordWriter<W>>),
+}
+
+impl<W: Write> Writer<W> {
+    fn write(&mut self, record: &Value) -> Result<()> {
+        match self {
+            Writer::Ndjson(out) => ndjson::write_record(out, record),
+            Writer::Yaml(out) => yaml::write_document(out, record),
+            Writer::Csv(writer) => writer.write(record),
+        }
+    }
+
+    fn finish(self) -> Result<()> {
+        match self {
+            Writer::Ndjson(mut out) | Writer::Yaml(mut out) => Ok(out.flush()?),
+            Writer::Csv(writer) => writer.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stream(input: &str, from: Format, to: Format) -> Result<String> {
+        let mut out = Vec::new();
+        convert(input.as_bytes(), &mut out, from, to, &Options::default())?;
+        Ok(String::from_utf8(out)?)
+    }
+
+    #[test]
+    fn conversions() {
+        let ndjson = "{\"a\":\"1\",\"b\":{\"c\":\"x\"}}\n{\"a\":\"2\",\"b\":{\"c\":\"y\"}}\n";
+        let csv = stream(ndjson, Format::Ndjson, Format::Csv).unwrap();
+        assert_eq!(csv, "a,b.c\n1,x\n2,y\n");
+        assert_eq!(stream(&csv, Format::Csv, Format::Ndjson).unwrap(), "{\"a\":\"1\",\"b.c\":\"x\"}\n{\"a\":\"2\",\"b.c\":\"y\"}\n");
+
+        let yaml = stream(ndjson, Format::Ndjson, Format::Yaml).unwrap();
+        assert_eq!(yaml, "---\na: '1'\nb:\n  c: x\n---\na: '2'\nb:\n  c: y\n");
+        assert_eq!(stream(&yaml, Format::Yaml, Format::Ndjson).unwrap(), ndjson);
+    }
+
+    #[test]
+    fn unstreamable() {
+        let err = stream("{}", Format::Json, Format::Ndjson).unwrap_err();
+        assert_eq!(err.to_string(), "json can't be streamed, only ndjson, csv, tsv or yaml");
+        assert!(stream("{}\n", Format::Ndjson, Format::Toml).is_err());
+    }
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+
+use super::pointer;
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+pub fn read(input: &str) -> Result<Value> {
+    ::toml::from_str(input).map_err(|err| match err.span() {
+        Some(span) => ParseError::at_offset(err.message().trim().replace('\n', ", "), input, span.start).into(),
+        None => err.into(),
+    })
+}
+
+pub fn write(value: &Value) -> Result<String> {
+    check(value, &mut Vec::new())?;
+    Ok(::toml::to_string(value)?)
+}
+
+/// finds what toml can't hold before the serializer
+/// does, since it doesn't say where.
+fn check(value: &Value, path: &mut Vec<String>) -> Result<(), ConvertError> {
+    match value {
+        _ if path.is_empty() && !matches!(value, Value::Object(_)) => {
+            Err(ConvertError::new(format!("toml needs a table at the top level, not {}", value.type_name()), "")
+                .with_help(r#"put it under a key, like {"items": ...}, or convert to yaml or json"#))
+        }
+        Value::Null => {
+            Err(ConvertError::new(format!("toml has no null, like the one at {}", pointer(path)), pointer(path))
+                .with_help("remove the key or give it a value, or convert to yaml or json, which have null"))
+        }
+        Value::Integer(i) if i64::try_from(*i).is_err() => {
+            Err(ConvertError::new(format!("toml integers are 64-bit, {i} at {} is too big", pointer(path)), pointer(path))
+                .with_help("make it a string"))
+        }
+        Value::Array(items) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.to_string());
+                check(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        Value::Object(map) => {
+            for (key, item) in map {
+                path.push(key.clone());
+                check(item, path)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn unrepresentable() {
+        let err = check(&json(r#"{"a": [1, {"b": null}]}"#), &mut Vec::new()).unwrap_err();
+        assert_eq!(err.pointer, "/a/1/b");
+        assert_eq!(err.to_string(), "toml has no null, like the one at /a/1/b");
+
+        let err = check(&json("[1]"), &mut Vec::new()).unwrap_err();
+        assert_eq!((err.pointer.as_str(), err.message.as_str()), ("", "toml needs a table at the top level, not array"));
+        assert!(check(&json(r#"{"n": 18446744073709551615}"#), &mut Vec::new()).is_err());
+    }
+
+    #[test]
+    fn read_error_position() {
+        let err = read("a = 1\nb = \n").unwrap_err();
+        let err = err.downcast::<ParseError>().unwrap();
+        assert_eq!((err.line, err.column), (2, Some(5)));
+    }
+}
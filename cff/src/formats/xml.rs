@@ -0,0 +1,378 @@
+//! xml has no types and no arrays, so it's mapped like so:
+//!
+//! - the document is an object with the root element as its only key
+//! - attributes become keys prefixed with `@`
+//! - text becomes `#text`, or the element's whole value if it has
+//!   no attributes or children. empty elements are null.
+//! - repeated elements become arrays, and so do the elements named
+//!   with --xml-array
+//! - data without a single top-level key is written inside a root
+//!   element (--xml-root), with array items as <item> elements
+//!
+//! writing does the reverse, so xml -> json -> xml gives the same
+//! elements back. the other way round loses some things:
+//!
+//! - every value comes back a string
+//! - text is trimmed, so " a " comes back "a"
+//! - empty strings and objects come back null
+//! - empty arrays aren't written, and one-item arrays come back as
+//!   the item unless named with --xml-array
+
+use anyhow::Result;
+use indexmap::IndexMap;
+use quick_xml::escape::EscapeError;
+use quick_xml::events::attributes::AttrError;
+use quick_xml::events::{BytesDecl, BytesStart, BytesText, Event};
+use quick_xml::{Reader, Writer};
+
+use super::{pointer, scalar_text, Options};
+use crate::diagnostic::{ConvertError, ParseError};
+use crate::Value;
+
+const ATTR_PREFIX: char = '@';
+const TEXT_KEY: &str = "#text";
+const DEFAULT_ROOT: &str = "root";
+/// element name for the items of a top-level array.
+const ITEM: &str = "item";
+
+/// an element that's still being read.
+struct Element {
+    name: String,
+    /// where its start tag is, as a byte offset
+    offset: usize,
+    attrs: IndexMap<String, Value>,
+    children: IndexMap<String, Value>,
+    text: String,
+}
+
+pub fn read(input: &str, options: &Options) -> Result<Value> {
+    let mut reader = Reader::from_str(input);
+
+    let mut stack: Vec<Element> = Vec::new();
+    let mut root = None;
+    loop {
+        let offset = reader.buffer_position() as usize;
+        let event = reader.read_event()
+            .map_err(|err| ParseError::at_offset(err.to_string(), input, reader.error_position() as usize))?;
+        match event {
+            Event::Start(start) => stack.push(Element::new(&start, input, offset)?),
+            Event::Empty(start) => {
+                let element = Element::new(&start, input, offset)?;
+                add(&mut stack, &mut root, element, input, options)?;
+            }
+            Event::End(_) => {
+                // quick-xml checks that end tags match
+                let element = stack.pop().ok_or_else(|| ParseError::at_offset("unexpected end tag", input, offset))?;
+                add(&mut stack, &mut root, element, input, options)?;
+            }
+            Event::Text(text) => {
+                let text = text.unescape().map_err(|err| {
+                    let (message, at) = unescape_error(err);
+                    ParseError::at_offset(message, input, offset + at)
+                })?;
+                // indentation between elements
+                if text.trim().is_empty() {
+                    continue;
+                }
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            }
+            Event::CData(data) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&data.decode()?);
+                }
+            }
+            Event::Eof => break,
+            // declarations, comments, processing instructions, doctypes
+            _ => {}
+        }
+    }
+    if let Some(open) = stack.last() {
+        return Err(ParseError::at_offset(format!("<{}> is never closed", open.name), input, open.offset).into());
+    }
+    root.ok_or_else(|| ParseError::at_offset("no root element", input, input.len()).into())
+}
+
+impl Element {
+    fn new(start: &BytesStart, input: &str, offset: usize) -> Result<Self> {
+        let name = String::from_utf8(start.name().as_ref().to_vec())?;
+        let mut attrs = IndexMap::new();
+        for attr in start.attributes() {
+            // positions are from just after the <
+            let attr = attr.map_err(|err| {
+                let (message, at) = attr_error(err);
+                ParseError::at_offset(message, input, offset + 1 + at)
+            })?;
+            let key = String::from_utf8(attr.key.as_ref().to_vec())?;
+            let value = attr.unescape_value().map_err(|err| {
+                // the position is within the value, so point at the tag
+                let (message, _) = unescape_error(err);
+                ParseError::at_offset(format!("{message} in the {key} attribute"), input, offset)
+            })?;
+            attrs.insert(format!("{ATTR_PREFIX}{key}"), Value::String(value.into_owned()));
+        }
+        Ok(Element { name, offset, attrs, children: IndexMap::new(), text: String::new() })
+    }
+
+    fn into_value(mut self) -> Value {
+        self.text = self.text.trim().to_string();
+        if self.attrs.is_empty() && self.children.is_empty() {
+            return match self.text.is_empty() {
+                true => Value::Null,
+                false => Value::String(self.text),
+            };
+        }
+        let mut map = self.attrs;
+        map.extend(self.children);
+        if !self.text.is_empty() {
+            map.insert(TEXT_KEY.to_string(), Value::String(self.text));
+        }
+        Value::Object(map)
+    }
+}
+
+/// what's wrong with some escaped text, and how
+/// far into it.
+fn unescape_error(err: quick_xml::Error) -> (String, usize) {
+    match err {
+        // the range is of the name, after the &
+        quick_xml::Error::Escape(EscapeError::UnrecognizedEntity(range, name)) => {
+            (format!("unrecognized entity &{name};"), range.start.saturating_sub(1))
+        }
+        quick_xml::Error::Escape(EscapeError::UnterminatedEntity(range)) => {
+            ("& starts an entity that has no ; to end it".to_string(), range.start)
+        }
+        err => (err.to_string(), 0),
+    }
+}
+
+/// what's wrong with an attribute, and how far into the tag.
+fn attr_error(err: AttrError) -> (String, usize) {
+    match err {
+        AttrError::ExpectedEq(at) => ("an attribute name must be followed by =".to_string(), at),
+        AttrError::ExpectedValue(at) => ("= must be followed by the attribute's value".to_string(), at),
+        AttrError::UnquotedValue(at) => ("attribute values must be in quotes".to_string(), at),
+        AttrError::ExpectedQuote(at, quote) => (format!("the attribute's value has no closing {}", quote as char), at),
+        AttrError::Duplicated(at, _) => ("the element already has this attribute".to_string(), at),
+    }
+}
+
+/// adds a finished element to its parent, or makes it the root.
+fn add(stack: &mut [Element], root: &mut Option<Value>, element: Element, input: &str, options: &Options) -> Result<()> {
+    let name = element.name.clone();
+    let offset = element.offset;
+    let always_array = options.xml_arrays.iter().any(|a| a == "*" || *a == name);
+    let value = element.into_value();
+    let Some(parent) = stack.last_mut() else {
+        if root.is_some() {
+            return Err(ParseError::at_offset("more than one root element", input, offset)
+                .with_help("xml has a single root element, so wrap them in one")
+                .into());
+        }
+        *root = Some(Value::Object(IndexMap::from([(name, value)])));
+        return Ok(());
+    };
+    match parent.children.get_mut(&name) {
+        Some(Value::Array(items)) => items.push(value),
+        Some(existing) => {
+            let first = std::mem::replace(existing, Value::Null);
+            *existing = Value::Array(vec![first, value]);
+        }
+        None if always_array => {
+            parent.children.insert(name, Value::Array(vec![value]));
+        }
+        None => {
+            parent.children.insert(name, value);
+        }
+    }
+    Ok(())
+}
+
+pub fn write(value: &Value, options: &Options) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    match value {
+        // already shaped like a document
+        Value::Object(map) if map.len() == 1 && !matches!(map[0], Value::Array(_)) => {
+            let (name, value) = map.first().unwrap();
+            write_element(&mut writer, name, value, &mut vec![name.clone()])?;
+        }
+        _ => {
+            let root = options.xml_root.as_deref().unwrap_or(DEFAULT_ROOT);
+            match value {
+                // one root element, not one per item
+                Value::Array(_) => {
+                    check_name(root, &[])?;
+                    writer.write_event(Event::Start(BytesStart::new(root)))?;
+                    write_element(&mut writer, ITEM, value, &mut Vec::new())?;
+                    writer.write_event(Event::End(BytesStart::new(root).to_end()))?;
+                }
+                _ => write_element(&mut writer, root, value, &mut Vec::new())?,
+            }
+        }
+    }
+    Ok(String::from_utf8(writer.into_inner())?)
+}
+
+/// path is where value is, for errors.
+fn write_element(writer: &mut Writer<Vec<u8>>, name: &str, value: &Value, path: &mut Vec<String>) -> Result<()> {
+    check_name(name, path)?;
+    match value {
+        Value::Null => {
+            writer.write_event(Event::Empty(BytesStart::new(name)))?;
+        }
+        Value::Array(items) => {
+            // an array is the same element repeated
+            for (i, item) in items.iter().enumerate() {
+                path.push(i.to_string());
+                if let Value::Array(_) = item {
+                    return Err(ConvertError::new(format!("xml can't hold arrays of arrays, like the one at {}", pointer(path)), pointer(path))
+                        .with_help("wrap each inner array in an object, like {\"items\": [...]}")
+                        .into());
+                }
+                write_element(writer, name, item, path)?;
+                path.pop();
+            }
+        }
+        Value::Object(map) => {
+            let mut start = BytesStart::new(name);
+            for (key, value) in map {
+                if let Some(attr) = key.strip_prefix(ATTR_PREFIX) {
+                    path.push(key.clone());
+                    check_name(attr, path)?;
+                    let value = scalar_text(value).ok_or_else(|| {
+                        ConvertError::new(format!("attribute {key} at {} must be a scalar, not {}", pointer(path), value.type_name()), pointer(path))
+                            .with_help(format!("keys starting with {ATTR_PREFIX} are written as attributes, so rename it to write an element"))
+                    })?;
+                    start.push_attribute((attr, value.as_str()));
+                    path.pop();
+                }
+            }
+            let mut body = map.iter().filter(|(key, _)| !key.starts_with(ATTR_PREFIX)).peekable();
+            if body.peek().is_none() {
+                writer.write_event(Event::Empty(start))?;
+                return Ok(());
+            }
+            writer.write_event(Event::Start(start))?;
+            for (key, value) in body {
+                path.push(key.clone());
+                if key == TEXT_KEY {
+                    let text = scalar_text(value).ok_or_else(|| {
+                        ConvertError::new(format!("{TEXT_KEY} at {} must be a scalar, not {}", pointer(path), value.type_name()), pointer(path))
+                            .with_help(format!("{TEXT_KEY} is written as the element's text, so rename it to write an element"))
+                    })?;
+                    writer.write_event(Event::Text(BytesText::new(&text)))?;
+                } else {
+                    write_element(writer, key, value, path)?;
+                }
+                path.pop();
+            }
+            writer.write_event(Event::End(BytesStart::new(name).to_end()))?;
+        }
+        scalar => {
+            let text = scalar_text(scalar).unwrap_or_default();
+            writer.create_element(name).write_text_content(BytesText::new(&text))?;
+        }
+    }
+    Ok(())
+}
+
+/// close enough to the xml spec to catch
+/// keys that can't be element names.
+fn check_name(name: &str, path: &[String]) -> Result<(), ConvertError> {
+    let valid = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == ':')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, '_' | ':' | '-' | '.'));
+    if !valid {
+        let at = match path.is_empty() {
+            true => "as the root element".to_string(),
+            false => format!("at {}", pointer(path)),
+        };
+        return Err(ConvertError::new(format!("{name:?} {at} can't be used as an xml name"), pointer(path))
+            .with_help("xml names start with a letter or _ and hold only letters, digits, _, :, - and ."));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_mapping() {
+        let input = r#"<?xml version="1.0"?>
+            <!-- a comment -->
+            <feed lang="en">
+              <title>news &amp; views</title>
+              <entry id="1"><title>a</title></entry>
+              <entry id="2"><title>b</title><link href="x"/></entry>
+              <note type="x">some <![CDATA[<raw>]]> text</note>
+              <empty/>
+            </feed>"#;
+        let expected = json(r##"{"feed": {
+            "@lang": "en",
+            "title": "news & views",
+            "entry": [
+                {"@id": "1", "title": "a"},
+                {"@id": "2", "title": "b", "link": {"@href": "x"}}
+            ],
+            "note": {"@type": "x", "#text": "some <raw> text"},
+            "empty": null
+        }}"##);
+        assert_eq!(read(input, &Options::default()).unwrap(), expected);
+    }
+
+    #[test]
+    fn array_coercion() {
+        let input = "<a><b>1</b><c>2</c></a>";
+        let options = Options { xml_arrays: vec!["b".to_string()], ..Default::default() };
+        assert_eq!(read(input, &options).unwrap(), json(r#"{"a": {"b": ["1"], "c": "2"}}"#));
+        let options = Options { xml_arrays: vec!["*".to_string()], ..Default::default() };
+        assert_eq!(read(input, &options).unwrap(), json(r#"{"a": {"b": ["1"], "c": ["2"]}}"#));
+    }
+
+    #[test]
+    fn write_and_read_back() {
+        let value = json(r##"{"feed": {"@lang": "en", "entry": [{"@id": 1, "title": "a<b"}, {"title": true}], "note": {"@type": "x", "#text": "hi"}, "empty": null}}"##);
+        let xml = write(&value, &Options::default()).unwrap();
+        assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>"), "{xml}");
+        assert!(xml.contains("<title>a&lt;b</title>"), "{xml}");
+        let expected = json(r##"{"feed": {"@lang": "en", "entry": [{"@id": "1", "title": "a<b"}, {"title": "true"}], "note": {"@type": "x", "#text": "hi"}, "empty": null}}"##);
+        assert_eq!(read(&xml, &Options::default()).unwrap(), expected);
+    }
+
+    #[test]
+    fn write_wraps_in_root() {
+        let xml = write(&json(r#"{"a": 1, "b": 2}"#), &Options::default()).unwrap();
+        assert!(xml.contains("<root>"), "{xml}");
+        let options = Options { xml_root: Some("config".to_string()), ..Default::default() };
+        let xml = write(&json("[1, 2]"), &options).unwrap();
+        assert_eq!(read(&xml, &Options::default()).unwrap(), json(r#"{"config": {"item": ["1", "2"]}}"#));
+    }
+
+    #[test]
+    fn bad_xml() {
+        assert!(read("<a><b></a>", &Options::default()).is_err(), "mismatched tags");
+        let position = |input: &str| {
+            let err = read(input, &Options::default()).unwrap_err().downcast::<ParseError>().unwrap();
+            (err.message, err.line, err.column)
+        };
+        assert_eq!(position("<a>\n  <b>x</b>\n"), ("<a> is never closed".to_string(), 1, Some(1)));
+        assert_eq!(position("<a/>\n<b/>"), ("more than one root element".to_string(), 2, Some(1)));
+        assert_eq!(position("<a>\n <b>&bogus;</b></a>"), ("unrecognized entity &bogus;".to_string(), 2, Some(5)));
+        assert_eq!(position("<a>\n <b x=\"1\" x=\"2\"/></a>"), ("the element already has this attribute".to_string(), 2, Some(11)));
+        assert_eq!(position("<a>\n <b x=\"&no;\"/></a>"), ("unrecognized entity &no; in the x attribute".to_string(), 2, Some(2)));
+        assert!(read("", &Options::default()).is_err(), "no root");
+        assert!(write(&json(r#"{"a b": 1}"#), &Options::default()).is_err(), "bad name");
+        let err = write(&json(r#"{"a": {"f": [[1, 2], [3]]}}"#), &Options::default()).unwrap_err();
+        let err = err.downcast::<ConvertError>().unwrap();
+        assert_eq!((err.message.as_str(), err.pointer.as_str()), ("xml can't hold arrays of arrays, like the one at /a/f/0", "/a/f/0"));
+        assert!(err.help.is_some());
+        let err = write(&json(r#"{"a": {"b c": 1}}"#), &Options::default()).unwrap_err();
+        assert_eq!(err.downcast::<ConvertError>().unwrap().pointer, "/a/b c");
+    }
+}
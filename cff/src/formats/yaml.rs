@@ -0,0 +1,169 @@
+use std::io::{BufRead, Lines, Write};
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use super::Options;
+use crate::diagnostic::ParseError;
+use crate::Value;
+
+/// a stream of more than one document reads as an array
+/// of them.
+pub fn read(input: &str) -> Result<Value> {
+    let mut docs = documents(input)?;
+    Ok(match docs.len() {
+        0 => Value::Null,
+        1 => docs.remove(0),
+        _ => Value::Array(docs),
+    })
+}
+
+/// each document, leaving out empty ones (e.g. after a
+/// trailing ---) when there's more than one.
+pub fn documents(input: &str) -> Result<Vec<Value>> {
+    let mut docs = serde_yaml::Deserializer::from_str(input)
+        .map(Value::deserialize)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| parse_error(err, 1))?;
+    if docs.len() > 1 {
+        docs.retain(|doc| *doc != Value::Null);
+    }
+    Ok(docs)
+}
+
+/// with --yaml-documents an array is written
+/// as a stream of documents.
+pub fn write(value: &Value, options: &Options) -> Result<String> {
+    match value {
+        Value::Array(docs) if options.yaml_documents => {
+            let mut out = Vec::new();
+            for doc in docs {
+                write_document(&mut out, doc)?;
+            }
+            Ok(String::from_utf8(out)?)
+        }
+        _ => Ok(serde_yaml::to_string(value)?),
+    }
+}
+
+/// the documents of a `---` separated stream, read one at a time
+/// so only the current document is held in memory.
+pub struct Documents<R> {
+    lines: Lines<R>,
+    /// lines read so far
+    line: usize,
+    /// text after a `---` that starts the next document
+    carry: Option<String>,
+}
+
+impl<R: BufRead> Documents<R> {
+    pub fn new(input: R) -> Self {
+        Documents { lines: input.lines(), line: 0, carry: None }
+    }
+}
+
+impl<R: BufRead> Iterator for Documents<R> {
+    type Item = Result<Value>;
+
+    fn next(&mut self) -> Option<Result<Value>> {
+        // a carried over document started on the separator's line
+        let mut start = match self.carry {
+            Some(_) => self.line,
+            None => self.line + 1,
+        };
+        let mut doc = self.carry.take().unwrap_or_default();
+        loop {
+            let line = match self.lines.next() {
+                Some(Ok(line)) => line,
+                Some(Err(err)) => return Some(Err(err.into())),
+                None => break,
+            };
+            self.line += 1;
+            let separator = line.starts_with("---") && line[3..].chars().next().is_none_or(char::is_whitespace);
+            let end = line.starts_with("...") && line[3..].trim().is_empty();
+            if !separator && !end {
+                doc.push_str(&line);
+                doc.push('\n');
+                continue;
+            }
+            // kept on its own line so lines in the document
+            // match lines in the stream
+            let rest = match separator {
+                true => format!("{}\n", &line[3..]),
+                false => "\n".to_string(),
+            };
+            if is_blank(&doc) {
+                // nothing before the first separator, or an empty document
+                doc = rest;
+                start = self.line;
+                continue;
+            }
+            self.carry = Some(rest);
+            break;
+        }
+        if is_blank(&doc) {
+            return None;
+        }
+        Some(serde_yaml::from_str(&doc).map_err(|err| parse_error(err, start)))
+    }
+}
+
+/// with the line made relative to the whole stream, for
+/// a document starting at line start.
+fn parse_error(err: serde_yaml::Error, start: usize) -> anyhow::Error {
+    match err.location() {
+        Some(at) => ParseError::from_serde(&err, at.line() + start - 1, at.column()).into(),
+        None => err.into(),
+    }
+}
+
+/// only whitespace and comments.
+fn is_blank(doc: &str) -> bool {
+    doc.lines().all(|line| line.trim().is_empty() || line.trim_start().starts_with('#'))
+}
+
+pub fn write_document(mut out: impl Write, value: &Value) -> Result<()> {
+    out.write_all(b"---\n")?;
+    serde_yaml::to_writer(&mut out, value)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn read_and_write_streams() {
+        let input = "kind: Service\n---\nkind: Deployment\n---\n";
+        let value = read(input).unwrap();
+        assert_eq!(value, json(r#"[{"kind": "Service"}, {"kind": "Deployment"}]"#));
+        assert_eq!(read("---\na: 1\n").unwrap(), json(r#"{"a": 1}"#), "one document");
+        assert_eq!(read("").unwrap(), Value::Null);
+
+        assert_eq!(write(&value, &Options::default()).unwrap(), "- kind: Service\n- kind: Deployment\n");
+        let options = Options { yaml_documents: true, ..Default::default() };
+        assert_eq!(write(&value, &options).unwrap(), "---\nkind: Service\n---\nkind: Deployment\n");
+    }
+
+    #[test]
+    fn documents() {
+        let input = "# leading comment\n---\na: 1\n--- b: 2\n---\n---\n- x\n...\n# trailing\n";
+        let docs: Vec<Value> = Documents::new(input.as_bytes()).collect::<Result<_>>().unwrap();
+        assert_eq!(docs, [json(r#"{"a": 1}"#), json(r#"{"b": 2}"#), json(r#"["x"]"#)]);
+        assert_eq!(Documents::new("a: 1\n".as_bytes()).count(), 1, "no separators");
+
+        let err = Documents::new("a: 1\n---\nb: [\n".as_bytes()).nth(1).unwrap().unwrap_err();
+        assert!(err.to_string().contains("at line 4 column 1"), "{err}");
+        let err = Documents::new("a: 1\n--- [1,\n".as_bytes()).nth(1).unwrap().unwrap_err();
+        assert!(err.to_string().contains("at line 3 column 1"), "{err}");
+
+        let mut out = Vec::new();
+        write_document(&mut out, &json(r#"{"a": 1}"#)).unwrap();
+        write_document(&mut out, &json("[1]")).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "---\na: 1\n---\n- 1\n");
+    }
+}
@@ -1,29 +1,37 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use anyhow::{anyhow, Result};
 use std::path::{PathBuf, Path};
-use std::fs::File;
-use std::io::{self, BufReader, Read, Write};
-use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, IsTerminal, Read, Write};
 
+mod diagnostic;
+mod edit;
+mod formats;
+mod merge;
+mod path;
+mod split;
+mod value;
 
-#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
-enum Format {
-    Json,
-    Yaml,
-    Toml,
-}
+pub use diagnostic::{ConvertError, ParseError};
+pub use formats::{Format, Options};
+pub use value::Value;
+
+use formats::parse_conversion;
 
 #[derive(Parser, Debug)]
 #[command(about)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// path to an optional source file.
     /// if no path is provided, will read from stdin.
-    #[clap(short, long)]
+    #[clap(short, long, global = true)]
     source_path: Option<PathBuf>,
 
     /// optional path to write conversion results.
     /// if no path is provided, will output to stdout.
-    #[clap(short, long)]
+    #[clap(short, long, global = true, visible_short_alias = 'o', visible_alias = "output")]
     dest_path: Option<PathBuf>,
 
     /// file conversion to do:
@@ -33,97 +41,412 @@ pub struct Args {
     ///     TJ: toml to json
     ///     YT: yaml to toml
     ///     TY: toml to yaml
-    #[clap(required=true, value_parser=parse_conversion)]
-    conversion: (Format, Format),
+    /// or use --from and --to. if neither is given, the
+    /// formats are taken from the file extensions, and
+    /// the source format is guessed from its content.
+    #[clap(value_parser=parse_conversion, conflicts_with_all=["from", "to"])]
+    conversion: Option<(Format, Format)>,
+
+    /// format to convert from
+    #[clap(short, long, value_enum, global = true)]
+    from: Option<Format>,
+
+    /// format to convert to
+    #[clap(short, long, value_enum, global = true)]
+    to: Option<Format>,
+
+    /// convert record by record instead of reading all
+    /// the input first, so memory use stays flat. works
+    /// between ndjson, csv, tsv and multi-document yaml.
+    #[clap(long)]
+    stream: bool,
+
+    /// write each yaml document (or array item) to its
+    /// own file, named by a template like
+    /// {kind}-{metadata.name}.json. {index} is the
+    /// document's position. the output format is taken
+    /// from the template's extension if --to isn't given.
+    #[clap(long, conflicts_with_all = ["dest_path", "stream"])]
+    split: Option<String>,
+
+    #[command(flatten)]
+    options: Options,
 }
 
-fn parse_conversion(conversion: &str) -> Result<(Format, Format)> {
-    let c = match conversion.to_uppercase().as_str() {
-        "JY" => (Format::Json, Format::Yaml),
-        "JT" => (Format::Json, Format::Toml),
-        "YJ" => (Format::Yaml, Format::Json),
-        "YT" => (Format::Yaml, Format::Toml),
-        "TJ" => (Format::Toml, Format::Json),
-        "TY" => (Format::Toml, Format::Yaml),
-        _ => {
-            return Err(anyhow!("conversion unsupported"));
-        }
-    };
-    Ok(c)
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// print what a path picks out of the input, like
+    /// package.version, spec.containers[*].image,
+    /// /items/0 or $..[?(@.kind == 'Service')].metadata.
+    /// strings are printed as they are and anything else
+    /// as json, one per line, unless --to is given. each
+    /// document of a yaml stream is looked in on its own.
+    Get {
+        path: String,
+    },
+    /// set the value at a path, making objects on the way
+    /// if they're missing. toml and yaml written as themselves
+    /// keep their comments. a path like items.- adds to the
+    /// end of a list.
+    Set {
+        path: String,
+        value: String,
+        /// read the value as json, like 3, true, null or
+        /// ["a", "b"], rather than as a string.
+        #[clap(long)]
+        json: bool,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// delete what a path picks out of the input.
+    Del {
+        path: String,
+        #[command(flatten)]
+        edit: EditArgs,
+    },
+    /// deep-merge files of any mix of formats, each one
+    /// layered over the ones before it. written as the
+    /// first file's format unless --to or -o say otherwise.
+    Merge {
+        #[clap(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        strategy: merge::Strategy,
+        /// print which file each value came from to stderr.
+        #[clap(long)]
+        provenance: bool,
+    },
 }
 
-fn yaml_to_json(data: &str) -> Result<String> {
-    let yaml_data: serde_yaml::Value = serde_yaml::from_str(data)?;
-    Ok(serde_json::to_string_pretty(&yaml_data)?)
+#[derive(clap::Args, Debug)]
+struct EditArgs {
+    /// write the result back to the source file.
+    #[clap(short, long)]
+    in_place: bool,
+
+    /// with --in-place, keep the original next to it, as
+    /// <file>.bak or with the suffix given.
+    #[clap(long, num_args = 0..=1, default_missing_value = ".bak", requires = "in_place")]
+    backup: Option<String>,
 }
 
-fn yaml_to_toml(data: &str) -> Result<String> {
-    let yaml_data: serde_yaml::Value = serde_yaml::from_str(data)?;
-    Ok(toml::to_string(&yaml_data)?)
+/// reads input as one format and writes it as another.
+pub fn convert(input: &[u8], from: Format, to: Format, options: &Options) -> Result<Vec<u8>> {
+    let value = from.read(input, options)?;
+    write(input, &value, from, to, options)
 }
 
-fn json_to_yaml(data: &str) -> Result<String> {
-    let json_data: serde_json::Value = serde_json::from_str(data)?;
-    Ok(serde_yaml::to_string(&json_data)?)
+/// writes the value read from input. toml and yaml written as
+/// themselves are edited instead, so their comments survive.
+fn write(input: &[u8], value: &Value, from: Format, to: Format, options: &Options) -> Result<Vec<u8>> {
+    if from == to {
+        if let Some(mut doc) = edit::Document::parse(from, input)? {
+            doc.normalize();
+            if options.sort_keys {
+                doc.sort_keys()?;
+            }
+            return Ok(doc.to_string().into_bytes());
+        }
+    }
+    to.write(value, options)
 }
 
-fn json_to_toml(data: &str) -> Result<String> {
-    let json_data: serde_json::Value = serde_json::from_str(data)?;
-    Ok(toml::to_string(&json_data)?)
+pub fn run(args: Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Get { path }) => return get(&args, path),
+        Some(Command::Set { path, value, json, edit }) => {
+            let value = match json {
+                true => serde_json::from_str(value)
+                    .map_err(|err| anyhow!("{value} isn't json ({err}), leave out --json to set it as a string"))?,
+                false => Value::String(value.clone()),
+            };
+            return change(&args, path, Some(value), edit);
+        }
+        Some(Command::Del { path, edit }) => return change(&args, path, None, edit),
+        Some(Command::Merge { files, strategy, provenance }) => return merge(&args, files, strategy, *provenance),
+        None => {}
+    }
+    if args.stream {
+        return stream(&args);
+    }
+
+    let data = read_input(&args)?;
+    let (from, to) = formats_for(&args, Some(&data))?;
+    let file = source_name(&args);
+    let value = from.read(&data, &args.options)
+        .map_err(|err| diagnostic::render(err, &file, &data))?;
+    if let Some(template) = &args.split {
+        let paths = split::write_documents(template, value, to, &args.options)
+            .map_err(|err| diagnostic::render(err, &file, &data))?;
+        for path in paths {
+            println!("{path}");
+        }
+        return Ok(());
+    }
+    let converted_data = write(&data, &value, from, to, &args.options)
+        .map_err(|err| diagnostic::render(err, &file, &data))?;
+    output(&args, to, &converted_data)
 }
 
-fn toml_to_json(data: &str) -> Result<String> {
-    let toml_data: toml::Value = toml::de::from_str(data)?;
-    Ok(serde_json::to_string_pretty(&toml_data)?)
+/// prints what a path picks out of the input, or out of
+/// each document of a multi-document yaml stream.
+fn get(args: &Args, expr: &str) -> Result<()> {
+    let path = path::Path::parse(expr)?;
+    let data = read_input(args)?;
+    let from = from_format(args, Some(&data))?;
+    let docs = from.documents(&data, &args.options)
+        .map_err(|err| diagnostic::render(err, &source_name(args), &data))?;
+    let found: Vec<&Value> = docs.iter().flat_map(|doc| path.query(doc)).collect();
+    if path.is_single() && found.is_empty() {
+        return Err(match &docs[..] {
+            [doc] => path.missing(doc),
+            docs => anyhow!("none of the {} documents has anything at {path}", docs.len()),
+        });
+    }
+
+    if args.to.is_some() || args.dest_path.is_some() {
+        let to = to_format(args)?;
+        let value = match path.is_single() && docs.len() == 1 {
+            true => found[0].clone(),
+            false => Value::Array(found.into_iter().cloned().collect()),
+        };
+        let data = to.write(&value, &args.options)
+            .map_err(|err| diagnostic::render(err, &source_name(args), &data))?;
+        return output(args, to, &data);
+    }
+    let mut stdout = io::stdout().lock();
+    for value in found {
+        match value {
+            Value::String(s) => writeln!(stdout, "{s}")?,
+            other => writeln!(stdout, "{}", serde_json::to_string(other)?)?,
+        }
+    }
+    Ok(())
 }
 
-fn toml_to_yaml(data: &str) -> Result<String> {
-    let toml_data: toml::Value = toml::de::from_str(data)?;
-    Ok(serde_yaml::to_string(&toml_data)?)
+/// sets (or with no value, deletes) what a path picks out of
+/// the input, editing toml and yaml where they're written.
+fn change(args: &Args, expr: &str, new: Option<Value>, flags: &EditArgs) -> Result<()> {
+    let source = match (&args.source_path, flags.in_place) {
+        (None, true) => return Err(anyhow!("--in-place needs a file to edit, use --source-path")),
+        (_, true) if args.dest_path.is_some() => return Err(anyhow!("--in-place writes to the source file, so leave out --dest-path")),
+        (source, _) => source,
+    };
+    let path = path::Path::parse(expr)?;
+    let data = read_input(args)?;
+    let from = from_format(args, Some(&data))?;
+    let file = source_name(args);
+    let mut value = from.read(&data, &args.options)
+        .map_err(|err| diagnostic::render(err, &file, &data))?;
+    let to = match (args.to, &args.dest_path) {
+        (None, None) => from,
+        _ => to_format(args)?,
+    };
+    if flags.in_place && to != from {
+        return Err(anyhow!("--in-place writes the source file as {}, so leave out --to {}", from.name(), to.name()));
+    }
+
+    let mut locations = path.locations(&value)?;
+    if new.is_none() {
+        if path.is_single() && path.query(&value).is_empty() {
+            return Err(path.missing(&value));
+        }
+        // later items first, so taking one out doesn't move the rest
+        locations.reverse();
+    }
+    let mut doc = match to == from {
+        true => edit::Document::parse(from, &data)?,
+        false => None,
+    };
+    for location in &locations {
+        match &new {
+            Some(new) => path::set(&mut value, location, new.clone())?,
+            None => {
+                path::remove(&mut value, location);
+            }
+        }
+        match (&mut doc, &new) {
+            (Some(doc), Some(_)) => doc.set(location, &value).map_err(|err| diagnostic::render(err, &file, &data))?,
+            (Some(doc), None) => doc.remove(location, &value),
+            (None, _) => {}
+        }
+    }
+    if let Some(doc) = &mut doc {
+        if args.options.sort_keys {
+            doc.sort_keys()?;
+        }
+    }
+    let out = match doc {
+        Some(doc) => doc.to_string().into_bytes(),
+        None => to.write(&value, &args.options).map_err(|err| diagnostic::render(err, &file, &data))?,
+    };
+
+    match source {
+        Some(source) if flags.in_place => {
+            if let Some(suffix) = &flags.backup {
+                let mut backup = source.clone().into_os_string();
+                backup.push(suffix);
+                fs::copy(source, &backup)?;
+            }
+            write_data(source, &out)
+        }
+        _ => output(args, to, &out),
+    }
 }
 
-type ConversionFn = fn(&str) -> Result<String>;
-type FormatPair = (Format, Format);
+/// layers each file over the ones before it.
+fn merge(args: &Args, files: &[PathBuf], strategy: &merge::Strategy, provenance: bool) -> Result<()> {
+    let names: Vec<String> = files.iter().map(|file| file.display().to_string()).collect();
+    let mut merged: Option<merge::Merged> = None;
+    let mut first = None;
+    for (n, file) in files.iter().enumerate() {
+        let data = fs::read(file).map_err(|err| anyhow!("can't read {}: {err}", file.display()))?;
+        let from = format_of(args.from, Some(file), Some(&data))?;
+        let value = from.read(&data, &args.options)
+            .map_err(|err| diagnostic::render(err, &names[n], &data))?;
+        match &mut merged {
+            Some(merged) => merged.merge(value, n, strategy)?,
+            None => {
+                merged = Some(merge::Merged::new(value, &names));
+                first = Some(from);
+            }
+        }
+    }
+    let (Some(merged), Some(first)) = (merged, first) else {
+        return Err(anyhow!("nothing to merge"));
+    };
+    if provenance {
+        let width = merged.provenance().iter().map(|(at, _)| at.len()).max().unwrap_or(0);
+        for (at, file) in merged.provenance() {
+            eprintln!("{at:width$}  {file}");
+        }
+    }
+    let to = match (args.to, &args.dest_path) {
+        (None, None) => first,
+        _ => to_format(args)?,
+    };
+    let data = to.write(&merged.value, &args.options)?;
+    output(args, to, &data)
+}
 
-pub fn run(args: Args) -> Result<()> {
-    let mut conversions: HashMap<FormatPair, ConversionFn> = HashMap::new();
-    conversions.insert((Format::Yaml, Format::Json), yaml_to_json); 
-    conversions.insert((Format::Yaml, Format::Toml), yaml_to_toml);
-    conversions.insert((Format::Json, Format::Yaml), json_to_yaml);
-    conversions.insert((Format::Json, Format::Toml), json_to_toml);
-    conversions.insert((Format::Toml, Format::Json), toml_to_json);
-    conversions.insert((Format::Toml, Format::Yaml), toml_to_yaml);
-
-    let conversion_fn = conversions.get(&args.conversion).ok_or_else(|| {
-        io::Error::new(io::ErrorKind::NotFound, "Conversion function not found")
-    })?;
-
-    let data = if let Some(path) = args.source_path {
-        let file = File::open(path)?;
-        let mut reader = BufReader::new(file);
-        let mut input = String::new();
-        reader.read_to_string(&mut input)?;
-        input
-    } else {
-        let mut input = String::new();
-        io::stdin().read_to_string(&mut input)?;
-        input
+/// all of the source file, or stdin.
+fn read_input(args: &Args) -> Result<Vec<u8>> {
+    let mut input = Vec::new();
+    match &args.source_path {
+        Some(path) => BufReader::new(File::open(path)?).read_to_end(&mut input)?,
+        None => io::stdin().read_to_end(&mut input)?,
     };
+    Ok(input)
+}
 
-    let converted_data = conversion_fn(&data)?;
-    if let Some(path) = args.dest_path {
-        write_data(&path, &converted_data)?;
+/// writes data to the dest path, or stdout if there isn't
+/// one. binary formats aren't written to a terminal.
+fn output(args: &Args, to: Format, data: &[u8]) -> Result<()> {
+    if let Some(path) = &args.dest_path {
+        write_data(path, data)?;
+    } else if to.is_binary() && !args.options.hex && !args.options.base64 {
+        let mut stdout = io::stdout();
+        if stdout.is_terminal() {
+            return Err(anyhow!("{} is binary, use --hex, --base64 or --dest-path to see it", to.name()));
+        }
+        stdout.write_all(data)?;
     } else {
-        println!("{}", &converted_data);
+        let mut stdout = io::stdout();
+        stdout.write_all(data)?;
+        if !data.ends_with(b"\n") {
+            writeln!(stdout)?;
+        }
     }
-
     Ok(())
 }
 
-fn write_data(path: impl AsRef<Path>, data: &str) -> Result<()> {
-    let mut file = File::create(path)?;
-    file.write_all(data.as_bytes())?;
-    Ok(())
+fn stream(args: &Args) -> Result<()> {
+    let (from, to) = formats_for(args, None)?;
+    let input: Box<dyn BufRead> = match &args.source_path {
+        Some(path) => Box::new(BufReader::new(File::open(path)?)),
+        None => Box::new(io::stdin().lock()),
+    };
+    let output: Box<dyn Write> = match &args.dest_path {
+        Some(path) => Box::new(BufWriter::new(File::create(path)?)),
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    };
+    // the input isn't kept, so there's no snippet to show
+    formats::stream::convert(input, output, from, to, &args.options)
+        .map_err(|err| diagnostic::render(err, &source_name(args), b""))
+}
+
+/// what to call the input in error messages.
+fn source_name(args: &Args) -> String {
+    match &args.source_path {
+        Some(path) => path.display().to_string(),
+        None => "<stdin>".to_string(),
+    }
+}
+
+/// the conversion code or --from/--to if given, otherwise
+/// whatever the file extensions (or the input) suggest.
+/// there's no input to go on when streaming.
+fn formats_for(args: &Args, input: Option<&[u8]>) -> Result<(Format, Format)> {
+    if let Some(conversion) = args.conversion {
+        return Ok(conversion);
+    }
+    Ok((from_format(args, input)?, to_format(args)?))
+}
+
+fn from_format(args: &Args, input: Option<&[u8]>) -> Result<Format> {
+    format_of(args.from, args.source_path.as_deref(), input)
+}
+
+/// --from if given, otherwise the format the file's extension
+/// (or its content) suggests.
+fn format_of(from: Option<Format>, path: Option<&Path>, input: Option<&[u8]>) -> Result<Format> {
+    let sniff = |input: Option<&[u8]>| match input {
+        Some(input) => Format::sniff(input),
+        None => Err(anyhow!("can't guess the input format when streaming, use --from")),
+    };
+    Ok(match (from, path) {
+        (Some(from), _) => from,
+        (None, Some(path)) => match Format::from_path(path) {
+            Some(from) => from,
+            None => sniff(input)
+                .map_err(|e| anyhow!("{}: {e}", path.display()))?,
+        },
+        (None, None) => sniff(input)?,
+    })
+}
+
+fn to_format(args: &Args) -> Result<Format> {
+    let dest = args.dest_path.as_deref().or(args.split.as_deref().map(Path::new));
+    Ok(match (args.to, dest) {
+        (Some(to), _) => to,
+        (None, Some(path)) => Format::from_path(path).ok_or_else(|| {
+            anyhow!("can't tell what format {} should be from its extension, use --to", path.display())
+        })?,
+        (None, None) => return Err(anyhow!("no output format given, use --to (or a conversion code like JY)")),
+    })
+}
+
+/// writes data to a file next to path and renames it over
+/// path, so a failed write can't leave half a file behind.
+/// symlinks are written through and permissions kept.
+fn write_data(path: impl AsRef<Path>, data: &[u8]) -> Result<()> {
+    let path = fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf());
+    let name = path.file_name().ok_or_else(|| anyhow!("{} isn't a file", path.display()))?;
+    let temp = path.with_file_name(format!(".{}.{}.tmp", name.to_string_lossy(), std::process::id()));
+    let written = (|| -> Result<()> {
+        let mut file = File::create(&temp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        if let Ok(metadata) = fs::metadata(&path) {
+            fs::set_permissions(&temp, metadata.permissions())?;
+        }
+        fs::rename(&temp, &path)?;
+        Ok(())
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written.map_err(|err| anyhow!("can't write {}: {err}", path.display()))
 }
 
 #[cfg(test)]
@@ -131,6 +454,10 @@ mod tests {
     use super::*;
     use std::fs;
 
+    fn convert_str(input: &str, from: Format, to: Format, options: &Options) -> Result<String> {
+        Ok(String::from_utf8(convert(input.as_bytes(), from, to, options)?)?)
+    }
+
     fn compare_json_str(expected: &str, actual: &str) {
         let expected_json: serde_json::Value = serde_json::from_str(expected).unwrap();
         let actual_json: serde_json::Value = serde_json::from_str(actual).unwrap();
@@ -152,7 +479,7 @@ mod tests {
     #[test]
     fn json_to_yaml_success() {
         let data = fs::read_to_string("tests/data/test.json").unwrap();
-        let yaml_data = json_to_yaml(&data).unwrap();
+        let yaml_data = convert_str(&data, Format::Json, Format::Yaml, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.yaml").unwrap();
         compare_yaml_str(&expected, &yaml_data);
     }
@@ -160,7 +487,7 @@ mod tests {
     #[test]
     fn json_to_toml_success() {
         let data = fs::read_to_string("tests/data/test.json").unwrap();
-        let toml_data = json_to_toml(&data).unwrap();
+        let toml_data = convert_str(&data, Format::Json, Format::Toml, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.toml").unwrap();
         compare_toml_str(&expected, &toml_data);
     }
@@ -168,7 +495,7 @@ mod tests {
     #[test]
     fn yaml_to_json_success() {
         let data = fs::read_to_string("tests/data/test.yaml").unwrap();
-        let json_data = yaml_to_json(&data).unwrap();
+        let json_data = convert_str(&data, Format::Yaml, Format::Json, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.json").unwrap();
         compare_json_str(&expected, &json_data);
     }
@@ -176,7 +503,7 @@ mod tests {
     #[test]
     fn yaml_to_toml_success() {
         let data = fs::read_to_string("tests/data/test.yaml").unwrap();
-        let toml_data = yaml_to_toml(&data).unwrap();
+        let toml_data = convert_str(&data, Format::Yaml, Format::Toml, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.toml").unwrap();
         compare_toml_str(&expected, &toml_data);
     }
@@ -184,7 +511,7 @@ mod tests {
     #[test]
     fn toml_to_json_success() {
         let data = fs::read_to_string("tests/data/test.toml").unwrap();
-        let json_data = toml_to_json(&data).unwrap();
+        let json_data = convert_str(&data, Format::Toml, Format::Json, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.json").unwrap();
         compare_json_str(&expected, &json_data);
     }
@@ -192,8 +519,247 @@ mod tests {
     #[test]
     fn toml_to_yaml_success() {
         let data = fs::read_to_string("tests/data/test.toml").unwrap();
-        let yaml_data = toml_to_yaml(&data).unwrap();
+        let yaml_data = convert_str(&data, Format::Toml, Format::Yaml, &Options::default()).unwrap();
         let expected = fs::read_to_string("tests/data/test.yaml").unwrap();
         compare_yaml_str(&expected, &yaml_data);
     }
+
+    fn compare_xml_str(expected: &str, actual: &str) {
+        let options = Options::default();
+        assert_eq!(Format::Xml.read(expected.as_bytes(), &options).unwrap(), Format::Xml.read(actual.as_bytes(), &options).unwrap())
+    }
+
+    #[test]
+    fn json_to_xml_success() {
+        let data = fs::read_to_string("tests/data/test.json").unwrap();
+        let xml_data = convert_str(&data, Format::Json, Format::Xml, &Options::default()).unwrap();
+        let expected = fs::read_to_string("tests/data/test.xml").unwrap();
+        compare_xml_str(&expected, &xml_data);
+    }
+
+    #[test]
+    fn xml_to_toml_success() {
+        let data = fs::read_to_string("tests/data/test.xml").unwrap();
+        let toml_data = convert_str(&data, Format::Xml, Format::Toml, &Options::default()).unwrap();
+        let expected = fs::read_to_string("tests/data/test.toml").unwrap();
+        compare_toml_str(&expected, &toml_data);
+    }
+
+    #[test]
+    fn xml_round_trip() {
+        let data = fs::read_to_string("tests/data/feed.xml").unwrap();
+        let json_data = convert_str(&data, Format::Xml, Format::Json, &Options::default()).unwrap();
+        let expected = fs::read_to_string("tests/data/feed.json").unwrap();
+        compare_json_str(&expected, &json_data);
+
+        let xml_data = convert_str(&json_data, Format::Json, Format::Xml, &Options::default()).unwrap();
+        compare_xml_str(&data, &xml_data);
+    }
+
+    #[test]
+    fn xml_round_trip_losses() {
+        let data = fs::read_to_string("tests/data/lossy.json").unwrap();
+        let xml_data = convert_str(&data, Format::Json, Format::Xml, &Options::default()).unwrap();
+        let json_data = convert_str(&xml_data, Format::Xml, Format::Json, &Options::default()).unwrap();
+        let expected = r#"{"doc": {"spaced": "spaced", "empty": null, "none": null, "table": null, "count": "3", "one": "a"}}"#;
+        compare_json_str(expected, &json_data);
+    }
+
+    #[test]
+    fn json_to_csv_success() {
+        let data = fs::read_to_string("tests/data/users.json").unwrap();
+        let csv_data = convert_str(&data, Format::Json, Format::Csv, &Options::default()).unwrap();
+        let expected = fs::read_to_string("tests/data/users.csv").unwrap();
+        assert_eq!(expected, csv_data);
+    }
+
+    #[test]
+    fn csv_to_json_success() {
+        let data = fs::read_to_string("tests/data/users.csv").unwrap();
+        let options = Options { infer_types: true, ..Default::default() };
+        let json_data = convert_str(&data, Format::Csv, Format::Json, &options).unwrap();
+        let expected = r#"[
+            {"id": 1, "name": "ada", "address.city": "london", "address.zip": "02134", "admin": true},
+            {"id": 2, "name": "grace", "address.city": "new york, ny", "address.zip": null, "admin": false}
+        ]"#;
+        compare_json_str(expected, &json_data);
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let data = fs::read("tests/data/test.json").unwrap();
+        for format in [Format::Msgpack, Format::Cbor, Format::Bson] {
+            let bytes = convert(&data, Format::Json, format, &Options::default()).unwrap();
+            let json_data = String::from_utf8(convert(&bytes, format, Format::Json, &Options::default()).unwrap()).unwrap();
+            compare_json_str(&String::from_utf8(data.clone()).unwrap(), &json_data);
+        }
+
+        let options = Options { hex: true, ..Default::default() };
+        let hex = convert(&data, Format::Json, Format::Msgpack, &options).unwrap();
+        assert!(hex.iter().all(u8::is_ascii_hexdigit));
+        let json_data = String::from_utf8(convert(&hex, Format::Msgpack, Format::Json, &options).unwrap()).unwrap();
+        compare_json_str(&String::from_utf8(data).unwrap(), &json_data);
+    }
+
+    #[test]
+    fn jsonc_to_json_success() {
+        let data = fs::read_to_string("tests/data/tsconfig.jsonc").unwrap();
+        let json_data = convert_str(&data, Format::Jsonc, Format::Json, &Options::default()).unwrap();
+        let expected = r#"{
+            "compilerOptions": {"target": "es2020", "strict": true, "paths": {"@/*": ["src/*"]}},
+            "include": ["src", "tests"]
+        }"#;
+        compare_json_str(expected, &json_data);
+
+        let err = convert_str(&data, Format::Json, Format::Yaml, &Options::default()).unwrap_err();
+        assert!(err.to_string().contains("use --from jsonc"), "{err}");
+    }
+
+    #[test]
+    fn ini_to_properties_success() {
+        let data = fs::read_to_string("tests/data/app.ini").unwrap();
+        let properties = convert_str(&data, Format::Ini, Format::Properties, &Options::default()).unwrap();
+        assert_eq!(properties, "name=cff\nserver.host=localhost\nserver.port=8080\nserver.tls.enabled=true\n");
+
+        let options = Options { infer_types: true, ..Default::default() };
+        let json_data = convert_str(&properties, Format::Properties, Format::Json, &options).unwrap();
+        let expected = r#"{"name": "cff", "server": {"host": "localhost", "port": 8080, "tls": {"enabled": true}}}"#;
+        compare_json_str(expected, &json_data);
+
+        let err = convert_str(&json_data, Format::Json, Format::Dotenv, &Options::default()).unwrap_err();
+        assert_eq!(err.to_string(), ".env only holds top-level keys with scalar values, /server is object");
+    }
+
+    #[test]
+    fn multi_document_yaml_to_ndjson_success() {
+        let data = fs::read_to_string("tests/data/manifests.yaml").unwrap();
+        let ndjson = convert_str(&data, Format::Yaml, Format::Ndjson, &Options::default()).unwrap();
+        let lines: Vec<&str> = ndjson.lines().collect();
+        assert_eq!(lines.len(), 2);
+        compare_json_str(r#"{"apiVersion": "apps/v1", "kind": "Deployment", "metadata": {"name": "web"}, "spec": {"replicas": 2}}"#, lines[1]);
+
+        let options = Options { yaml_documents: true, ..Default::default() };
+        let yaml = convert_str(&ndjson, Format::Ndjson, Format::Yaml, &options).unwrap();
+        assert_eq!(yaml.matches("---\n").count(), 2);
+        compare_json_str(&convert_str(&data, Format::Yaml, Format::Json, &options).unwrap(), &convert_str(&yaml, Format::Yaml, Format::Json, &options).unwrap());
+    }
+
+    #[test]
+    fn toml_and_yaml_keep_comments() {
+        let options = Options { sort_keys: true, ..Default::default() };
+        let data = fs::read_to_string("tests/data/config.toml").unwrap();
+        let toml = convert_str(&data, Format::Toml, Format::Toml, &options).unwrap();
+        let expected = "# cff's own settings\n\n# only what's needed\n[dependencies]\nanyhow = \"1.0\"\nclap = { features = [\"derive\"], version = \"4.5\" }\nserde = \"1.0\"\n\n[package]\n# keep in step with ci\nedition = \"2021\"\nname = \"cff\" # the binary\nversion = \"0.1.0\"\n";
+        assert_eq!(toml, expected);
+
+        let data = fs::read_to_string("tests/data/config.yaml").unwrap();
+        let yaml = convert_str(&data, Format::Yaml, Format::Yaml, &options).unwrap();
+        let expected = "# deployed by ci\n\nenv:\n  DEBUG: \"false\"\n  # where logs go\n  LOG: stdout\nservice:\n  # bump with each release\n  image: nginx:1.27\n  name: web # public name\n  ports:\n    - 80\n    - 443\n";
+        assert_eq!(yaml, expected);
+        assert_eq!(convert_str(&data, Format::Yaml, Format::Yaml, &Options::default()).unwrap(), data.replace("web   #", "web #"));
+    }
+
+    #[test]
+    fn conversions_keep_key_order() {
+        fn keys(value: &Value, out: &mut Vec<String>) {
+            if let Value::Object(map) = value {
+                for (key, value) in map {
+                    out.push(key.clone());
+                    keys(value, out);
+                }
+            }
+        }
+        let data = br#"{"zeta": 1, "kappa": "k", "alpha": {"mu": "x", "beta": "b"}, "delta": {"c": "c", "b": {"z": "z", "a": "a"}}}"#;
+        let expected = ["zeta", "kappa", "alpha", "mu", "beta", "delta", "c", "b", "z", "a"];
+        let formats = [Format::Json, Format::Json5, Format::Hjson, Format::Ron, Format::Yaml, Format::Toml, Format::Xml, Format::Ini, Format::Properties, Format::Msgpack, Format::Cbor, Format::Bson];
+        for format in formats {
+            let written = convert(data, Format::Json, format, &Options::default()).unwrap();
+            let mut found = Vec::new();
+            keys(&format.read(&written, &Options::default()).unwrap(), &mut found);
+            // xml wraps several top-level keys in a root element
+            if format == Format::Xml {
+                found.remove(0);
+            }
+            assert_eq!(found, expected, "{}", format.name());
+        }
+    }
+
+    #[test]
+    fn get_from_any_format() {
+        let path = path::Path::parse("$..[?(@.kind == 'Deployment')].spec.replicas").unwrap();
+        let data = fs::read("tests/data/manifests.yaml").unwrap();
+        let value = Format::Yaml.read(&data, &Options::default()).unwrap();
+        assert_eq!(path.query(&value), [&Value::Integer(2)]);
+
+        let path = path::Path::parse("package.version").unwrap();
+        let value = Format::Toml.read(&fs::read("tests/data/config.toml").unwrap(), &Options::default()).unwrap();
+        assert_eq!(path.query(&value), [&Value::String("0.1.0".to_string())]);
+
+        // xml reads everything as text, so filters compare numbers loosely
+        let path = path::Path::parse("feed.entry[?(@.@id > 1)].title").unwrap();
+        let value = Format::Xml.read(&fs::read("tests/data/feed.xml").unwrap(), &Options::default()).unwrap();
+        assert_eq!(path.query(&value), [&Value::String("rq learns caching".to_string())]);
+    }
+
+    #[test]
+    fn get_from_each_document() {
+        let dir = std::env::temp_dir().join(format!("cff-get-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.json");
+        let get = |expr: &str| {
+            let args = ["cff", "get", expr, "-s", "tests/data/deployments.yaml", "-d", out.to_str().unwrap()];
+            run(Args::parse_from(args)).map(|_| fs::read_to_string(&out).unwrap())
+        };
+        let images = get("spec.template.spec.containers[*].image").unwrap();
+        compare_json_str(r#"["nginx:1.27", "exporter:0.9", "worker:2"]"#, &images);
+        compare_json_str(r#"["web", "worker"]"#, &get("metadata.name").unwrap());
+        assert_eq!(get("spec.nope").unwrap_err().to_string(), "none of the 2 documents has anything at /spec/nope");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn edits_in_place() {
+        let dir = std::env::temp_dir().join(format!("cff-edit-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("config.yaml");
+        fs::copy("tests/data/config.yaml", &file).unwrap();
+        let cff = |args: &[&str]| run(Args::parse_from(["cff"].iter().chain(args).copied().chain(["-s", file.to_str().unwrap()])));
+
+        cff(&["set", "service.replicas", "3", "--json", "-i", "--backup"]).unwrap();
+        cff(&["del", "env.DEBUG", "--in-place"]).unwrap();
+        let edited = fs::read_to_string(&file).unwrap();
+        assert!(edited.starts_with("# deployed by ci\n\nservice:\n  name: web   # public name\n"));
+        assert!(edited.contains("    - 443\n  replicas: 3\nenv:\n  # where logs go\n  LOG: stdout\n"));
+        assert!(!edited.contains("DEBUG"));
+        assert_eq!(fs::read(dir.join("config.yaml.bak")).unwrap(), fs::read("tests/data/config.yaml").unwrap());
+
+        assert!(cff(&["del", "env.nope", "-i"]).is_err());
+        let err = cff(&["set", "service.name", "api", "-i", "--to", "json"]).unwrap_err();
+        assert_eq!(err.to_string(), "--in-place writes the source file as yaml, so leave out --to json");
+        assert!(cff(&["set", "env", "{", "--json", "-i"]).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), edited, "left alone when it fails");
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2, "no temporary files left");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn merges_files_of_any_format() {
+        let dir = std::env::temp_dir().join(format!("cff-merge-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let out = dir.join("out.json");
+        let files = ["tests/data/merge/base.yaml", "tests/data/merge/overlay.toml", "tests/data/merge/env.json"];
+        let args = ["cff", "merge"].into_iter().chain(files).chain(["--arrays", "merge-by-key=name", "-o", out.to_str().unwrap()]);
+        run(Args::parse_from(args)).unwrap();
+        let expected = r#"{
+            "service": {"name": "web", "replicas": 3, "ports": [80, 443]},
+            "containers": [{"name": "app", "image": "app:1.1"}, {"name": "sidecar", "image": "proxy:2.1"}, {"name": "metrics", "image": "exporter:0.9"}],
+            "log": {"level": "debug", "format": "json"}
+        }"#;
+        compare_json_str(expected, &fs::read_to_string(&out).unwrap());
+
+        let args = ["cff", "merge"].into_iter().chain(files).chain(["--conflicts", "error"]);
+        let err = run(Args::parse_from(args)).unwrap_err();
+        assert_eq!(err.to_string(), "/service/replicas is 1 in tests/data/merge/base.yaml but 3 in tests/data/merge/overlay.toml");
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,293 @@
+//! deep merging of values read from several files, later ones
+//! layered over earlier ones, keeping track of which file each
+//! part of the result came from.
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+
+use crate::formats::pointer;
+use crate::Value;
+
+#[derive(Debug, Clone, clap::Args)]
+pub struct Strategy {
+    /// how arrays in later files combine with earlier ones:
+    ///     replace: the later array takes the place of the earlier
+    ///     append: its items are added to the end
+    ///     merge-by-key=<key>: items with the same <key> are
+    ///         merged, and the others added to the end
+    #[clap(long, default_value = "replace", value_parser = parse_arrays)]
+    pub arrays: Arrays,
+
+    /// what to do when files give a value two different ways:
+    /// override (the later file wins), keep (the first one
+    /// wins) or error.
+    #[clap(long, value_enum, default_value_t)]
+    pub conflicts: Conflicts,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arrays {
+    Replace,
+    Append,
+    MergeByKey(String),
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Conflicts {
+    #[default]
+    Override,
+    Keep,
+    Error,
+}
+
+fn parse_arrays(s: &str) -> Result<Arrays, String> {
+    match s.split_once('=') {
+        Some(("merge-by-key", "")) => Err("merge-by-key needs a key, like merge-by-key=name".to_string()),
+        Some(("merge-by-key", key)) => Ok(Arrays::MergeByKey(key.to_string())),
+        None if s == "replace" => Ok(Arrays::Replace),
+        None if s == "append" => Ok(Arrays::Append),
+        _ => Err("expected replace, append or merge-by-key=<key>".to_string()),
+    }
+}
+
+/// which file (by its place in the list) each part of a
+/// merged value came from.
+#[derive(Debug, Clone, PartialEq)]
+enum Origin {
+    /// all of it, from the one file
+    File(usize),
+    Object(IndexMap<String, Origin>),
+    Array(Vec<Origin>),
+}
+
+pub struct Merged<'a> {
+    pub value: Value,
+    origin: Origin,
+    /// what to call each file in errors and provenance
+    files: &'a [String],
+}
+
+impl<'a> Merged<'a> {
+    /// the first file's value.
+    pub fn new(value: Value, files: &'a [String]) -> Merged<'a> {
+        Merged { value, origin: Origin::File(0), files }
+    }
+
+    /// layers the value of the file at index file over what's
+    /// been merged so far.
+    pub fn merge(&mut self, value: Value, file: usize, strategy: &Strategy) -> Result<()> {
+        let mut merge = Merge { strategy, file, files: self.files, path: Vec::new() };
+        merge.value(&mut self.value, &mut self.origin, value)
+    }
+
+    /// the location of each scalar (or empty array or object)
+    /// in the result, and the file it came from.
+    pub fn provenance(&self) -> Vec<(String, &str)> {
+        let mut out = Vec::new();
+        leaves(&self.value, &self.origin, &mut Vec::new(), &mut |path, file| {
+            let at = match path.is_empty() {
+                true => "/".to_string(),
+                false => pointer(path),
+            };
+            out.push((at, self.files[file].as_str()));
+        });
+        out
+    }
+}
+
+struct Merge<'s> {
+    strategy: &'s Strategy,
+    file: usize,
+    files: &'s [String],
+    path: Vec<String>,
+}
+
+impl Merge<'_> {
+    fn value(&mut self, target: &mut Value, origin: &mut Origin, new: Value) -> Result<()> {
+        match (target, new) {
+            (Value::Object(target), Value::Object(new)) => {
+                let origins = origin.object(target);
+                for (key, new) in new {
+                    match target.get_mut(&key) {
+                        Some(target) => {
+                            self.path.push(key.clone());
+                            let origin = origins.entry(key).or_insert(Origin::File(0));
+                            self.value(target, origin, new)?;
+                            self.path.pop();
+                        }
+                        None => {
+                            origins.insert(key.clone(), Origin::File(self.file));
+                            target.insert(key, new);
+                        }
+                    }
+                }
+                Ok(())
+            }
+            (Value::Array(target), Value::Array(new)) if self.strategy.arrays != Arrays::Replace => {
+                let origins = origin.array(target);
+                for item in new {
+                    let same = match (&self.strategy.arrays, &item) {
+                        (Arrays::MergeByKey(key), Value::Object(map)) => map.get(key).and_then(|id| {
+                            target.iter().position(|other| matches!(other, Value::Object(other) if other.get(key) == Some(id)))
+                        }),
+                        _ => None,
+                    };
+                    match same {
+                        Some(i) => {
+                            self.path.push(i.to_string());
+                            self.value(&mut target[i], &mut origins[i], item)?;
+                            self.path.pop();
+                        }
+                        None => {
+                            target.push(item);
+                            origins.push(Origin::File(self.file));
+                        }
+                    }
+                }
+                Ok(())
+            }
+            (target, new) if *target == new => {
+                if self.strategy.conflicts == Conflicts::Override {
+                    *origin = Origin::File(self.file);
+                }
+                Ok(())
+            }
+            (target, new) => match self.strategy.conflicts {
+                Conflicts::Override => {
+                    *target = new;
+                    *origin = Origin::File(self.file);
+                    Ok(())
+                }
+                Conflicts::Keep => Ok(()),
+                Conflicts::Error => Err(anyhow!(
+                    "{} is {} in {} but {} in {}",
+                    match self.path.is_empty() {
+                        true => "the top level".to_string(),
+                        false => pointer(&self.path),
+                    },
+                    describe(target),
+                    self.files[origin.first()],
+                    describe(&new),
+                    self.files[self.file],
+                )),
+            },
+        }
+    }
+}
+
+impl Origin {
+    /// the origins of an object's values, split up if they were
+    /// all from one file.
+    fn object(&mut self, value: &IndexMap<String, Value>) -> &mut IndexMap<String, Origin> {
+        if let Origin::File(file) = *self {
+            *self = Origin::Object(value.keys().map(|key| (key.clone(), Origin::File(file))).collect());
+        }
+        match self {
+            Origin::Object(origins) => origins,
+            _ => unreachable!("an object's origin is a file or an object"),
+        }
+    }
+
+    fn array(&mut self, value: &[Value]) -> &mut Vec<Origin> {
+        if let Origin::File(file) = *self {
+            *self = Origin::Array(vec![Origin::File(file); value.len()]);
+        }
+        match self {
+            Origin::Array(origins) => origins,
+            _ => unreachable!("an array's origin is a file or an array"),
+        }
+    }
+
+    /// the earliest file any of it came from.
+    fn first(&self) -> usize {
+        match self {
+            Origin::File(file) => *file,
+            Origin::Object(origins) => origins.values().map(Origin::first).min().unwrap_or(0),
+            Origin::Array(origins) => origins.iter().map(Origin::first).min().unwrap_or(0),
+        }
+    }
+}
+
+fn leaves(value: &Value, origin: &Origin, path: &mut Vec<String>, f: &mut impl FnMut(&[String], usize)) {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(map) if !map.is_empty() => map.iter().map(|(key, value)| (key.clone(), value)).collect(),
+        Value::Array(items) if !items.is_empty() => items.iter().enumerate().map(|(i, item)| (i.to_string(), item)).collect(),
+        _ => return f(path, origin.first()),
+    };
+    for (key, child) in children {
+        let origin = match origin {
+            Origin::Object(origins) => origins.get(&key).unwrap_or(origin),
+            Origin::Array(origins) => key.parse().ok().and_then(|i: usize| origins.get(i)).unwrap_or(origin),
+            Origin::File(_) => origin,
+        };
+        path.push(key);
+        leaves(child, origin, path, f);
+        path.pop();
+    }
+}
+
+/// a value for an error message, short enough to read.
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Object(_) | Value::Array(_) => value.type_name().to_string(),
+        other => serde_json::to_string(other).unwrap_or_else(|_| other.type_name().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    fn merged(files: &[&str], arrays: Arrays, conflicts: Conflicts) -> Result<(Value, Vec<(String, String)>)> {
+        let names: Vec<String> = (0..files.len()).map(|n| format!("f{n}")).collect();
+        let strategy = Strategy { arrays, conflicts };
+        let mut merged = Merged::new(json(files[0]), &names);
+        for (n, file) in files.iter().enumerate().skip(1) {
+            merged.merge(json(file), n, &strategy)?;
+        }
+        let provenance = merged.provenance().into_iter().map(|(at, file)| (at, file.to_string())).collect();
+        Ok((merged.value, provenance))
+    }
+
+    #[test]
+    fn merges_objects_deeply() {
+        let files = [r#"{"a": {"b": 1, "c": [1, 2]}, "d": "x"}"#, r#"{"a": {"c": [3], "e": true}}"#, r#"{"d": "y"}"#];
+        let (value, provenance) = merged(&files, Arrays::Replace, Conflicts::Override).unwrap();
+        assert_eq!(value, json(r#"{"a": {"b": 1, "c": [3], "e": true}, "d": "y"}"#));
+        let files_of = |at: &str| provenance.iter().find(|(p, _)| p == at).map(|(_, file)| file.as_str());
+        assert_eq!(files_of("/a/b"), Some("f0"));
+        assert_eq!(files_of("/a/c/0"), Some("f1"));
+        assert_eq!(files_of("/d"), Some("f2"));
+
+        let (value, _) = merged(&files, Arrays::Append, Conflicts::Keep).unwrap();
+        assert_eq!(value, json(r#"{"a": {"b": 1, "c": [1, 2, 3], "e": true}, "d": "x"}"#));
+
+        let err = merged(&files, Arrays::Append, Conflicts::Error).unwrap_err();
+        assert_eq!(err.to_string(), "/d is \"x\" in f0 but \"y\" in f2");
+        assert!(merged(&[r#"{"a": 1}"#, r#"{"a": 1}"#], Arrays::Replace, Conflicts::Error).is_ok(), "the same value twice");
+    }
+
+    #[test]
+    fn merges_arrays_by_key() {
+        let files = [
+            r#"{"services": [{"name": "web", "port": 80}, {"name": "db", "port": 5432}]}"#,
+            r#"{"services": [{"name": "web", "port": 8080}, {"name": "cache"}, "x"]}"#,
+        ];
+        let (value, provenance) = merged(&files, Arrays::MergeByKey("name".to_string()), Conflicts::Override).unwrap();
+        assert_eq!(value, json(r#"{"services": [{"name": "web", "port": 8080}, {"name": "db", "port": 5432}, {"name": "cache"}, "x"]}"#));
+        let expected = [("/services/0/name", "f1"), ("/services/0/port", "f1"), ("/services/1/name", "f0"), ("/services/1/port", "f0"), ("/services/2/name", "f1"), ("/services/3", "f1")];
+        assert_eq!(provenance, expected.map(|(at, file)| (at.to_string(), file.to_string())));
+    }
+
+    #[test]
+    fn parses_array_strategies() {
+        assert_eq!(parse_arrays("append"), Ok(Arrays::Append));
+        assert_eq!(parse_arrays("merge-by-key=id"), Ok(Arrays::MergeByKey("id".to_string())));
+        assert!(parse_arrays("merge-by-key=").is_err());
+        assert!(parse_arrays("merge").is_err());
+    }
+}
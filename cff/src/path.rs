@@ -0,0 +1,666 @@
+//! paths into a [`Value`]. three spellings are understood:
+//! dotted (`spec.containers[*].image`, `items.0.name`), json
+//! pointers (`/spec/containers/0/image`) and jsonpath
+//! (`$..containers[?(@.name == 'web')].image`).
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use anyhow::{anyhow, Result};
+use indexmap::IndexMap;
+
+use crate::formats::pointer;
+use crate::Value;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    /// an object key, or an array index if it's a number
+    Key(String),
+    /// from the end if negative
+    Index(i64),
+    Slice(Option<i64>, Option<i64>),
+    Wildcard,
+    /// the value and everything under it, for `..`
+    Descend,
+    /// the children the filter is true for
+    Filter(Filter),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    Or(Box<Filter>, Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+    /// true if the operand is there and isn't false or null
+    Test(Operand),
+    Compare(Operand, Comparison, Operand),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    /// @, and steps from it
+    Current(Vec<Step>),
+    Literal(Value),
+}
+
+/// one step to a particular place in a value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Path {
+    pub fn parse(expr: &str) -> Result<Path> {
+        let expr = expr.trim();
+        if let Some(pointer) = expr.strip_prefix('/') {
+            let steps = pointer.split('/').map(|key| Step::Key(key.replace("~1", "/").replace("~0", "~"))).collect();
+            return Ok(Path { steps });
+        }
+        let mut parser = Parser { expr, at: 0 };
+        parser.eat("$");
+        let steps = parser.steps(true)?;
+        if parser.at < expr.len() {
+            return Err(parser.error("unexpected text"));
+        }
+        Ok(Path { steps })
+    }
+
+    /// whether the path names one value, rather than picking any
+    /// number of them with wildcards, slices or filters.
+    pub fn is_single(&self) -> bool {
+        self.steps.iter().all(|step| matches!(step, Step::Key(_) | Step::Index(_)))
+    }
+
+    /// the values the path leads to, in document order.
+    pub fn query<'v>(&self, value: &'v Value) -> Vec<&'v Value> {
+        select(&self.steps, value).into_iter().map(|(_, value)| value).collect()
+    }
+
+    /// where the path leads in value. a single path leads to one
+    /// place even if there's nothing there yet, and a key of - on
+    /// an array is the place after its last item.
+    pub fn locations(&self, value: &Value) -> Result<Vec<Vec<Segment>>> {
+        if !self.is_single() {
+            return Ok(select(&self.steps, value).into_iter().map(|(location, _)| location).collect());
+        }
+        let mut location = Vec::new();
+        let mut current = Some(value);
+        for step in &self.steps {
+            let segment = match (step, current) {
+                (Step::Key(key), Some(Value::Array(items))) if key == "-" => Segment::Index(items.len()),
+                (Step::Key(key), Some(Value::Array(_))) => match key.parse() {
+                    Ok(i) => Segment::Index(i),
+                    Err(_) => return Err(anyhow!("{} is an array, so {key} isn't in it", at(&location))),
+                },
+                (Step::Key(key), _) => Segment::Key(key.clone()),
+                (Step::Index(i), Some(Value::Array(items))) => match index(*i, items.len()) {
+                    Some(i) => Segment::Index(i),
+                    None if *i >= 0 => Segment::Index(*i as usize),
+                    None => return Err(anyhow!("{} has {} items, so nothing at {i}", at(&location), items.len())),
+                },
+                (Step::Index(i), _) => Segment::Index(usize::try_from(*i).map_err(|_| anyhow!("nothing at {i} in {}", at(&location)))?),
+                _ => unreachable!("single paths only have keys and indexes"),
+            };
+            current = current.and_then(|value| segment.child(value));
+            location.push(segment);
+        }
+        Ok(vec![location])
+    }
+
+    /// why a single path leads nowhere in value.
+    pub fn missing(&self, value: &Value) -> anyhow::Error {
+        let mut value = value;
+        let mut path: Vec<String> = Vec::new();
+        for step in &self.steps {
+            let Some((_, next)) = step.children(value).into_iter().next() else {
+                let at = at(&path);
+                return match (step, value) {
+                    (Step::Key(key), Value::Object(map)) => {
+                        let keys: Vec<&str> = map.keys().map(String::as_str).collect();
+                        anyhow!("{at} has no key {key}, only {}", keys.join(", "))
+                    }
+                    (_, Value::Array(items)) => anyhow!("{at} has {} items, so nothing at {step}", items.len()),
+                    _ => anyhow!("{at} is {}, so nothing at {step}", value.type_name()),
+                };
+            };
+            path.push(step.to_string());
+            value = next;
+        }
+        anyhow!("nothing at {self}")
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", pointer(&self.steps.iter().map(Step::to_string).collect::<Vec<_>>()))
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Step::Key(key) => write!(f, "{key}"),
+            Step::Index(i) => write!(f, "{i}"),
+            Step::Slice(start, end) => {
+                let bound = |b: &Option<i64>| b.map(|b| b.to_string()).unwrap_or_default();
+                write!(f, "{}:{}", bound(start), bound(end))
+            }
+            Step::Wildcard => write!(f, "*"),
+            Step::Descend => write!(f, ".."),
+            Step::Filter(_) => write!(f, "?(...)"),
+        }
+    }
+}
+
+impl fmt::Display for Segment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Segment::Key(key) => write!(f, "{key}"),
+            Segment::Index(i) => write!(f, "{i}"),
+        }
+    }
+}
+
+impl Segment {
+    fn child<'v>(&self, value: &'v Value) -> Option<&'v Value> {
+        match (self, value) {
+            (Segment::Key(key), Value::Object(map)) => map.get(key),
+            (Segment::Index(i), Value::Array(items)) => items.get(*i),
+            _ => None,
+        }
+    }
+}
+
+/// a location as a json pointer, or the top level.
+fn at(location: &[impl fmt::Display]) -> String {
+    match location.is_empty() {
+        true => "the top level".to_string(),
+        false => pointer(&location.iter().map(ToString::to_string).collect::<Vec<_>>()),
+    }
+}
+
+/// puts new at location, making objects (or arrays, for an
+/// index of 0) on the way where there's nothing or null. an
+/// index just past the end of an array adds an item.
+pub fn set(root: &mut Value, location: &[Segment], new: Value) -> Result<()> {
+    let mut current = root;
+    for (n, segment) in location.iter().enumerate() {
+        if matches!(current, Value::Null) {
+            *current = match segment {
+                Segment::Key(_) => Value::Object(IndexMap::new()),
+                Segment::Index(_) => Value::Array(Vec::new()),
+            };
+        }
+        match (&*current, segment) {
+            (Value::Object(_), Segment::Key(_)) => {}
+            (Value::Array(items), Segment::Index(i)) if *i <= items.len() => {}
+            (Value::Array(items), Segment::Index(i)) => {
+                return Err(anyhow!("{} has {} items, so there's no {i} to set", at(&location[..n]), items.len()));
+            }
+            (other, segment) => return Err(anyhow!("{} is {}, so it has no {segment}", at(&location[..n]), other.type_name())),
+        }
+        current = match (current, segment) {
+            (Value::Object(map), Segment::Key(key)) => map.entry(key.clone()).or_insert(Value::Null),
+            (Value::Array(items), Segment::Index(i)) => {
+                if *i == items.len() {
+                    items.push(Value::Null);
+                }
+                &mut items[*i]
+            }
+            _ => unreachable!("checked above"),
+        };
+    }
+    *current = new;
+    Ok(())
+}
+
+/// takes out what's at location, if anything is.
+pub fn remove(root: &mut Value, location: &[Segment]) -> Option<Value> {
+    let (last, parent) = location.split_last()?;
+    let parent = parent.iter().try_fold(root, |value, segment| match (value, segment) {
+        (Value::Object(map), Segment::Key(key)) => map.get_mut(key),
+        (Value::Array(items), Segment::Index(i)) => items.get_mut(*i),
+        _ => None,
+    })?;
+    match (parent, last) {
+        (Value::Object(map), Segment::Key(key)) => map.shift_remove(key),
+        (Value::Array(items), Segment::Index(i)) if *i < items.len() => Some(items.remove(*i)),
+        _ => None,
+    }
+}
+
+/// the values steps lead to, with where they are.
+fn select<'v>(steps: &[Step], value: &'v Value) -> Vec<(Vec<Segment>, &'v Value)> {
+    let mut current = vec![(Vec::new(), value)];
+    for step in steps {
+        current = current
+            .into_iter()
+            .flat_map(|(location, value)| {
+                step.children(value).into_iter().map(move |(relative, child)| {
+                    let mut location = location.clone();
+                    location.extend(relative);
+                    (location, child)
+                })
+            })
+            .collect();
+    }
+    current
+}
+
+impl Step {
+    /// what the step picks out of value, and where each
+    /// one is from there.
+    fn children<'v>(&self, value: &'v Value) -> Vec<(Vec<Segment>, &'v Value)> {
+        let item = |i: usize| Segment::Index(i).child(value).map(|child| (vec![Segment::Index(i)], child));
+        match (self, value) {
+            (Step::Key(key), Value::Object(map)) => map.get(key).map(|child| (vec![Segment::Key(key.clone())], child)).into_iter().collect(),
+            (Step::Key(key), Value::Array(_)) => key.parse().ok().and_then(item).into_iter().collect(),
+            (Step::Index(i), Value::Array(items)) => index(*i, items.len()).and_then(item).into_iter().collect(),
+            (Step::Slice(start, end), Value::Array(items)) => {
+                let len = items.len() as i64;
+                let bound = |b: Option<i64>, default: i64| match b {
+                    Some(b) if b < 0 => (len + b).max(0),
+                    Some(b) => b.min(len),
+                    None => default,
+                };
+                let (start, end) = (bound(*start, 0), bound(*end, len));
+                (start..end.max(start)).filter_map(|i| item(i as usize)).collect()
+            }
+            (Step::Wildcard, _) => values(value),
+            (Step::Descend, _) => {
+                let mut all = vec![(Vec::new(), value)];
+                for (location, child) in values(value) {
+                    for (relative, descendant) in Step::Descend.children(child) {
+                        all.push(([location.clone(), relative].concat(), descendant));
+                    }
+                }
+                all
+            }
+            (Step::Filter(filter), _) => values(value).into_iter().filter(|(_, child)| filter.matches(child)).collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// an array's items or an object's values.
+fn values(value: &Value) -> Vec<(Vec<Segment>, &Value)> {
+    match value {
+        Value::Array(items) => items.iter().enumerate().map(|(i, item)| (vec![Segment::Index(i)], item)).collect(),
+        Value::Object(map) => map.iter().map(|(key, value)| (vec![Segment::Key(key.clone())], value)).collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn index(i: i64, len: usize) -> Option<usize> {
+    let i = if i < 0 { len as i64 + i } else { i };
+    usize::try_from(i).ok().filter(|i| *i < len)
+}
+
+impl Filter {
+    fn matches(&self, value: &Value) -> bool {
+        match self {
+            Filter::Or(a, b) => a.matches(value) || b.matches(value),
+            Filter::And(a, b) => a.matches(value) && b.matches(value),
+            Filter::Not(filter) => !filter.matches(value),
+            Filter::Test(operand) => operand.resolve(value).is_some_and(|v| !matches!(v, Value::Null | Value::Bool(false))),
+            Filter::Compare(a, comparison, b) => {
+                let (Some(a), Some(b)) = (a.resolve(value), b.resolve(value)) else {
+                    return false;
+                };
+                let order = compare(a, b);
+                match comparison {
+                    Comparison::Eq => order == Some(Ordering::Equal),
+                    Comparison::Ne => order != Some(Ordering::Equal),
+                    Comparison::Lt => order == Some(Ordering::Less),
+                    Comparison::Le => matches!(order, Some(Ordering::Less | Ordering::Equal)),
+                    Comparison::Gt => order == Some(Ordering::Greater),
+                    Comparison::Ge => matches!(order, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+        }
+    }
+}
+
+impl Operand {
+    fn resolve<'v>(&'v self, value: &'v Value) -> Option<&'v Value> {
+        match self {
+            Operand::Current(steps) => select(steps, value).into_iter().next().map(|(_, value)| value),
+            Operand::Literal(literal) => Some(literal),
+        }
+    }
+}
+
+/// how two values compare. numbers read as text, as from
+/// xml, csv or ini, compare as numbers.
+fn compare(a: &Value, b: &Value) -> Option<Ordering> {
+    match (a, b) {
+        (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+        (Value::Bool(a), Value::Bool(b)) => Some(a.cmp(b)),
+        (Value::Null, Value::Null) => Some(Ordering::Equal),
+        (Value::Bool(_), Value::String(_)) | (Value::String(_), Value::Bool(_)) => {
+            text(a).zip(text(b)).map(|(a, b)| a.cmp(&b))
+        }
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+fn number(value: &Value) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Float(f) => Some(*f),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+fn text(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    expr: &'a str,
+    /// byte offset into expr
+    at: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.expr[self.at..]
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        let found = self.rest().starts_with(token);
+        if found {
+            self.at += token.len();
+        }
+        found
+    }
+
+    fn skip_space(&mut self) {
+        self.at = self.expr.len() - self.rest().trim_start().len();
+    }
+
+    fn error(&self, message: &str) -> anyhow::Error {
+        anyhow!("{message} at column {} of the path {:?}", self.expr[..self.at].chars().count() + 1, self.expr)
+    }
+
+    /// steps up to the end, or anything that can't start one.
+    /// top is whether a bare key can come first, as in a.b.
+    fn steps(&mut self, top: bool) -> Result<Vec<Step>> {
+        let mut steps = Vec::new();
+        if top && !self.rest().is_empty() && !self.rest().starts_with(['.', '[']) {
+            steps.push(self.name()?);
+        }
+        loop {
+            if self.eat("..") {
+                steps.push(Step::Descend);
+                if !self.rest().starts_with('[') {
+                    steps.push(self.name()?);
+                }
+            } else if self.eat(".") {
+                steps.push(self.name()?);
+            } else if self.eat("[") {
+                steps.push(self.bracket()?);
+            } else {
+                return Ok(steps);
+            }
+        }
+    }
+
+    /// a key up to the next . or [, or *.
+    fn name(&mut self) -> Result<Step> {
+        let end = self.rest().find(|c: char| matches!(c, '.' | '[' | ']' | ')' | '=' | '!' | '<' | '>' | '&' | '|') || c.is_whitespace());
+        let name = &self.rest()[..end.unwrap_or(self.rest().len())];
+        if name.is_empty() {
+            return Err(self.error("expected a key"));
+        }
+        self.at += name.len();
+        Ok(match name {
+            "*" => Step::Wildcard,
+            name => Step::Key(name.to_string()),
+        })
+    }
+
+    /// what's in [...], after the [.
+    fn bracket(&mut self) -> Result<Step> {
+        self.skip_space();
+        let step = if self.eat("*") {
+            Step::Wildcard
+        } else if self.eat("?") {
+            self.skip_space();
+            let filter = self.or()?;
+            Step::Filter(filter)
+        } else if self.rest().starts_with(['\'', '"']) {
+            Step::Key(self.string()?)
+        } else {
+            let start = self.integer();
+            self.skip_space();
+            if self.eat(":") {
+                self.skip_space();
+                let end = self.integer();
+                Step::Slice(start, end)
+            } else {
+                Step::Index(start.ok_or_else(|| self.error("expected an index, a 'key', * or a ?(filter)"))?)
+            }
+        };
+        self.skip_space();
+        if !self.eat("]") {
+            return Err(self.error("expected ]"));
+        }
+        Ok(step)
+    }
+
+    fn integer(&mut self) -> Option<i64> {
+        let rest = self.rest();
+        let digits = rest.strip_prefix('-').unwrap_or(rest);
+        let len = rest.len() - digits.len() + digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        let number = rest[..len].parse().ok()?;
+        self.at += len;
+        Some(number)
+    }
+
+    /// a quoted string, with backslash escapes.
+    fn string(&mut self) -> Result<String> {
+        let quote = self.rest().chars().next().unwrap_or_default();
+        let mut out = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => out.extend(chars.next().map(|(_, c)| c)),
+                c if c == quote => {
+                    self.at += i + 1;
+                    return Ok(out);
+                }
+                c => out.push(c),
+            }
+        }
+        Err(self.error("unclosed quote"))
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut filter = self.and()?;
+        while self.eat("||") {
+            filter = Filter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut filter = self.not()?;
+        while self.eat("&&") {
+            filter = Filter::And(Box::new(filter), Box::new(self.not()?));
+        }
+        Ok(filter)
+    }
+
+    fn not(&mut self) -> Result<Filter> {
+        self.skip_space();
+        let filter = if self.rest().starts_with('!') && !self.rest().starts_with("!=") {
+            self.at += 1;
+            Filter::Not(Box::new(self.not()?))
+        } else if self.eat("(") {
+            let filter = self.or()?;
+            self.skip_space();
+            if !self.eat(")") {
+                return Err(self.error("expected )"));
+            }
+            filter
+        } else {
+            let a = self.operand()?;
+            self.skip_space();
+            match self.comparison() {
+                Some(comparison) => {
+                    self.skip_space();
+                    Filter::Compare(a, comparison, self.operand()?)
+                }
+                None => Filter::Test(a),
+            }
+        };
+        self.skip_space();
+        Ok(filter)
+    }
+
+    fn comparison(&mut self) -> Option<Comparison> {
+        let comparisons = [
+            ("==", Comparison::Eq),
+            ("!=", Comparison::Ne),
+            ("<=", Comparison::Le),
+            (">=", Comparison::Ge),
+            ("<", Comparison::Lt),
+            (">", Comparison::Gt),
+        ];
+        comparisons.into_iter().find(|(token, _)| self.eat(token)).map(|(_, comparison)| comparison)
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        if self.eat("@") {
+            return Ok(Operand::Current(self.steps(false)?));
+        }
+        if self.rest().starts_with(['\'', '"']) {
+            return Ok(Operand::Literal(Value::String(self.string()?)));
+        }
+        let end = self.rest().find(|c: char| matches!(c, ')' | ']' | '=' | '!' | '<' | '>' | '&' | '|') || c.is_whitespace());
+        let literal = &self.rest()[..end.unwrap_or(self.rest().len())];
+        let value = match serde_json::from_str(literal) {
+            Ok(value) if !literal.is_empty() => value,
+            _ => return Err(self.error("expected @, a number, a 'string', true, false or null")),
+        };
+        self.at += literal.len();
+        Ok(Operand::Literal(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    fn get(expr: &str, value: &Value) -> Vec<Value> {
+        Path::parse(expr).unwrap().query(value).into_iter().cloned().collect()
+    }
+
+    #[test]
+    fn spellings() {
+        let value = json(r#"{"package": {"version": "1.0", "a.b": 1}, "items": [{"name": "x"}, {"name": "y"}]}"#);
+        assert_eq!(get("package.version", &value), [json(r#""1.0""#)]);
+        assert_eq!(get("$.package.version", &value), [json(r#""1.0""#)]);
+        assert_eq!(get("/package/version", &value), [json(r#""1.0""#)]);
+        assert_eq!(get("package['a.b']", &value), [json("1")]);
+        assert_eq!(get("items.1.name", &value), [json(r#""y""#)]);
+        assert_eq!(get("items[-1].name", &value), [json(r#""y""#)]);
+        assert_eq!(get("/items/0/name", &value), [json(r#""x""#)]);
+        assert!(get("items.2", &value).is_empty());
+        assert!(Path::parse("items.1.name").unwrap().is_single());
+        assert!(!Path::parse("items[*].name").unwrap().is_single());
+    }
+
+    #[test]
+    fn wildcards_slices_and_descent() {
+        let value = json(r#"{"spec": {"containers": [{"image": "a"}, {"image": "b"}, {"image": "c"}]}, "init": {"image": "d"}}"#);
+        assert_eq!(get("spec.containers[*].image", &value), [json(r#""a""#), json(r#""b""#), json(r#""c""#)]);
+        assert_eq!(get("spec.containers.*.image", &value), get("spec.containers[*].image", &value));
+        assert_eq!(get("spec.containers[1:].image", &value), [json(r#""b""#), json(r#""c""#)]);
+        assert_eq!(get("spec.containers[:-2].image", &value), [json(r#""a""#)]);
+        assert_eq!(get("$..image", &value).len(), 4);
+        assert_eq!(get("*", &value).len(), 2);
+    }
+
+    #[test]
+    fn filters() {
+        let value = json(r#"[
+            {"kind": "Deployment", "replicas": 3, "ready": true},
+            {"kind": "Service", "port": "8080"},
+            {"kind": "Deployment", "replicas": 1}
+        ]"#);
+        let kinds = |expr: &str| get(expr, &value).len();
+        assert_eq!(kinds("[?(@.kind == 'Deployment')]"), 2);
+        assert_eq!(kinds("[?@.replicas > 1]"), 1);
+        assert_eq!(kinds("[?(@.ready)]"), 1);
+        assert_eq!(kinds("[?(!@.ready && @.kind != \"Service\")]"), 1);
+        assert_eq!(kinds("[?(@.port == 8080 || @.replicas <= 1)]"), 2, "numbers read as text");
+        assert_eq!(get("$[?(@.kind == 'Deployment')].replicas", &value), [json("3"), json("1")]);
+    }
+
+    #[test]
+    fn errors() {
+        let err = Path::parse("a[?(@.b == )]").unwrap_err();
+        assert_eq!(err.to_string(), "expected @, a number, a 'string', true, false or null at column 12 of the path \"a[?(@.b == )]\"");
+        assert!(Path::parse("a[x]").is_err());
+        assert!(Path::parse("a.").is_err());
+
+        let value = json(r#"{"package": {"name": "cff", "version": "1"}, "list": [1]}"#);
+        let missing = |expr: &str| Path::parse(expr).unwrap().missing(&value).to_string();
+        assert_eq!(missing("package.versoin"), "/package has no key versoin, only name, version");
+        assert_eq!(missing("list[3]"), "/list has 1 items, so nothing at 3");
+        assert_eq!(missing("package.name.first"), "/package/name is string, so nothing at first");
+    }
+
+    #[test]
+    fn locations_set_and_remove() {
+        let mut value = json(r#"{"a": {"b": 1}, "list": [{"x": 1}, {"x": 2}], "empty": null}"#);
+        let locations = |expr: &str, value: &Value| Path::parse(expr).unwrap().locations(value).unwrap();
+        let key = |key: &str| Segment::Key(key.to_string());
+        assert_eq!(locations("list[-1].x", &value), [vec![key("list"), Segment::Index(1), key("x")]]);
+        assert_eq!(locations("list.-", &value), [vec![key("list"), Segment::Index(2)]]);
+        assert_eq!(locations("list[*].x", &value).len(), 2);
+        assert!(Path::parse("list.x").unwrap().locations(&value).is_err());
+
+        for (expr, new) in [("a.c.d", "true"), ("list.-", "3"), ("empty.k", "\"v\""), ("list[0].x", "0")] {
+            let location = locations(expr, &value).remove(0);
+            set(&mut value, &location, json(new)).unwrap();
+        }
+        assert_eq!(value, json(r#"{"a": {"b": 1, "c": {"d": true}}, "list": [{"x": 0}, {"x": 2}, 3], "empty": {"k": "v"}}"#));
+        let err = set(&mut value, &[key("a"), key("b"), key("c")], json("1")).unwrap_err();
+        assert_eq!(err.to_string(), "/a/b is integer, so it has no c");
+        let err = set(&mut value, &[key("list"), Segment::Index(5)], json("1")).unwrap_err();
+        assert_eq!(err.to_string(), "/list has 3 items, so there's no 5 to set");
+
+        assert_eq!(remove(&mut value, &[key("list"), Segment::Index(1)]), Some(json(r#"{"x": 2}"#)));
+        assert_eq!(remove(&mut value, &[key("a"), key("nope")]), None);
+        assert_eq!(remove(&mut value, &[key("a")]), Some(json(r#"{"b": 1, "c": {"d": true}}"#)));
+        assert_eq!(value, json(r#"{"list": [{"x": 0}, 3], "empty": {"k": "v"}}"#));
+    }
+}
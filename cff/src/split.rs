@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::{Format, Options, Value};
+
+/// writes each document (array item) to its own file, named
+/// by filling in the template's {placeholders}. returns the
+/// paths written. nothing is written unless every document
+/// has a name of its own and converts.
+pub fn write_documents(template: &str, value: Value, to: Format, options: &Options) -> Result<Vec<String>> {
+    let docs = match value {
+        Value::Array(docs) => docs,
+        doc => vec![doc],
+    };
+    let mut names = HashSet::new();
+    let mut files = Vec::with_capacity(docs.len());
+    for (i, doc) in docs.iter().enumerate() {
+        let path = file_name(template, doc, i)?;
+        // two documents with the same name would overwrite each other
+        if !names.insert(path.clone()) {
+            return Err(anyhow!("document {i} would also be written to {path}, add something like {{index}} to the template"));
+        }
+        let data = to.write(doc, options).with_context(|| format!("document {i}"))?;
+        files.push((path, data));
+    }
+    let mut paths = Vec::with_capacity(files.len());
+    for (path, data) in files {
+        if let Some(dir) = Path::new(&path).parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, data).with_context(|| format!("can't write {path}"))?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
+
+/// fills in {a.b.0}-style paths into the document, and
+/// {index} with its position in the stream.
+fn file_name(template: &str, doc: &Value, index: usize) -> Result<String> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..].find('}')
+            .ok_or_else(|| anyhow!("{{ isn't closed in the template {template}"))?;
+        let path = &rest[start + 1..start + end];
+        let text = match path {
+            "index" => index.to_string(),
+            _ => lookup(doc, path)
+                .and_then(scalar)
+                .ok_or_else(|| anyhow!("document {index} has no {path} to name its file with"))?,
+        };
+        // values can't add directories or climb out of them
+        if text == "." || text == ".." {
+            return Err(anyhow!("document {index} has {text:?} at {path}, which can't name a file"));
+        }
+        name.push_str(&text.replace(['/', '\\'], "_"));
+        rest = &rest[start + end + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
+
+fn lookup<'v>(doc: &'v Value, path: &str) -> Option<&'v Value> {
+    path.split('.').try_fold(doc, |value, key| match value {
+        Value::Object(map) => map.get(key),
+        Value::Array(items) => items.get(key.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Integer(i) => Some(i.to_string()),
+        Value::Float(f) => Some(f.to_string()),
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn json(input: &str) -> Value {
+        serde_json::from_str(input).unwrap()
+    }
+
+    #[test]
+    fn file_names() {
+        let doc = json(r#"{"kind": "Service", "metadata": {"name": "web/api", "ports": [80]}}"#);
+        assert_eq!(file_name("{kind}-{metadata.name}.json", &doc, 0).unwrap(), "Service-web_api.json");
+        assert_eq!(file_name("out/{index}-{metadata.ports.0}.yaml", &doc, 3).unwrap(), "out/3-80.yaml");
+        assert_eq!(file_name("{metadata.namespace}.json", &doc, 2).unwrap_err().to_string(), "document 2 has no metadata.namespace to name its file with");
+        assert!(file_name("{metadata}.json", &doc, 0).is_err(), "not a scalar");
+        assert!(file_name("{kind.json", &doc, 0).is_err());
+        let doc = json(r#"{"metadata": {"name": ".."}}"#);
+        assert_eq!(file_name("out/{metadata.name}/x.json", &doc, 1).unwrap_err().to_string(), r#"document 1 has ".." at metadata.name, which can't name a file"#);
+    }
+
+    #[test]
+    fn writes_each_document() {
+        let dir = std::env::temp_dir().join(format!("cff-split-{}", std::process::id()));
+        let template = format!("{}/{{kind}}.json", dir.display());
+        let value = json(r#"[{"kind": "a", "n": 1}, {"kind": "b"}]"#);
+        let paths = write_documents(&template, value, Format::Json, &Options::default()).unwrap();
+        assert_eq!(paths.len(), 2);
+        assert_eq!(fs::read_to_string(dir.join("a.json")).unwrap(), "{\n  \"kind\": \"a\",\n  \"n\": 1\n}");
+
+        let value = json(r#"[{"kind": "c"}, {"kind": "c"}]"#);
+        assert!(write_documents(&template, value, Format::Json, &Options::default()).is_err(), "same name twice");
+        assert!(!dir.join("c.json").exists(), "nothing written when a name is taken twice");
+        let value = json(r#"[{"kind": "d"}, {"kind": "e", "x": null}]"#);
+        assert!(write_documents(&template, value, Format::Toml, &Options::default()).is_err(), "toml has no null");
+        assert!(!dir.join("d.json").exists(), "nothing written when a document doesn't convert");
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,230 @@
+use std::fmt;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use indexmap::IndexMap;
+use serde::de::{self, Deserialize, Deserializer, EnumAccess, MapAccess, SeqAccess, VariantAccess, Visitor};
+use serde::ser::{Serialize, Serializer};
+
+/// how toml hands datetimes to serde.
+const TOML_DATETIME_KEY: &str = "$__toml_private_datetime";
+
+/// what every format reads into and writes from, so
+/// any format can be converted to any other. objects
+/// keep their keys in the order they were read.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    /// wide enough for both i64 and u64
+    Integer(i128),
+    Float(f64),
+    String(String),
+    /// from binary formats. text formats
+    /// get them base64 encoded.
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Object(IndexMap<String, Value>),
+}
+
+impl Value {
+    /// the name used in error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Null => "null",
+            Value::Bool(_) => "bool",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Bytes(_) => "bytes",
+            Value::Array(_) => "array",
+            Value::Object(_) => "object",
+        }
+    }
+
+    /// sorts the keys of every object, all the way down.
+    pub fn sort_keys(&mut self) {
+        match self {
+            Value::Array(items) => items.iter_mut().for_each(Value::sort_keys),
+            Value::Object(map) => {
+                map.sort_keys();
+                map.values_mut().for_each(Value::sort_keys);
+            }
+            _ => {}
+        }
+    }
+
+    /// scalars as they'd appear as an object key.
+    fn into_key(self) -> Option<String> {
+        match self {
+            Value::Null => Some("null".to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+            Value::Integer(i) => Some(i.to_string()),
+            Value::Float(f) => Some(f.to_string()),
+            Value::String(s) => Some(s),
+            Value::Bytes(b) => Some(STANDARD.encode(b)),
+            Value::Array(_) | Value::Object(_) => None,
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Integer(i) => {
+                // not every format supports 128 bit integers
+                if let Ok(i) = i64::try_from(*i) {
+                    serializer.serialize_i64(i)
+                } else if let Ok(u) = u64::try_from(*i) {
+                    serializer.serialize_u64(u)
+                } else {
+                    serializer.serialize_i128(*i)
+                }
+            }
+            Value::Float(f) => serializer.serialize_f64(*f),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Bytes(b) if serializer.is_human_readable() => serializer.serialize_str(&STANDARD.encode(b)),
+            Value::Bytes(b) => serializer.serialize_bytes(b),
+            Value::Array(items) => serializer.collect_seq(items),
+            Value::Object(map) => serializer.collect_map(map),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, b: bool) -> Result<Value, E> {
+        Ok(Value::Bool(b))
+    }
+
+    fn visit_i64<E>(self, i: i64) -> Result<Value, E> {
+        Ok(Value::Integer(i.into()))
+    }
+
+    fn visit_u64<E>(self, u: u64) -> Result<Value, E> {
+        Ok(Value::Integer(u.into()))
+    }
+
+    fn visit_i128<E>(self, i: i128) -> Result<Value, E> {
+        Ok(Value::Integer(i))
+    }
+
+    fn visit_f64<E>(self, f: f64) -> Result<Value, E> {
+        Ok(Value::Float(f))
+    }
+
+    fn visit_str<E>(self, s: &str) -> Result<Value, E> {
+        Ok(Value::String(s.to_string()))
+    }
+
+    fn visit_string<E>(self, s: String) -> Result<Value, E> {
+        Ok(Value::String(s))
+    }
+
+    fn visit_bytes<E>(self, b: &[u8]) -> Result<Value, E> {
+        Ok(Value::Bytes(b.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, b: Vec<u8>) -> Result<Value, E> {
+        Ok(Value::Bytes(b))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        Deserialize::deserialize(deserializer)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let mut items = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+        while let Some(item) = seq.next_element()? {
+            items.push(item);
+        }
+        Ok(Value::Array(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Value, A::Error> {
+        let mut map = IndexMap::with_capacity(access.size_hint().unwrap_or(0));
+        while let Some(key) = access.next_key::<Value>()? {
+            let key = key.into_key()
+                .ok_or_else(|| de::Error::custom("object keys must be strings, numbers or bools"))?;
+            if key == TOML_DATETIME_KEY {
+                // datetimes have no equivalent elsewhere, keep the text
+                return Ok(Value::String(access.next_value()?));
+            }
+            let value = access.next_value()?;
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    /// tagged values (e.g. yaml's `!Ref x`)
+    /// become { "Ref": "x" }.
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<Value, A::Error> {
+        let (tag, variant) = data.variant::<String>()?;
+        let value = variant.newtype_variant()?;
+        Ok(Value::Object(IndexMap::from([(tag, value)])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_key_order() {
+        let value: Value = serde_json::from_str(r#"{"b": 1, "a": {"d": null, "c": [true, 1.5]}}"#).unwrap();
+        let Value::Object(map) = &value else { panic!("not an object") };
+        assert_eq!(map.keys().collect::<Vec<_>>(), ["b", "a"]);
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"b":1,"a":{"d":null,"c":[true,1.5]}}"#);
+    }
+
+    #[test]
+    fn sort_keys() {
+        let mut value: Value = serde_json::from_str(r#"{"b": [{"z": 1, "y": 2}], "a": {"d": null, "c": 1}}"#).unwrap();
+        value.sort_keys();
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"a":{"c":1,"d":null},"b":[{"y":2,"z":1}]}"#);
+    }
+
+    #[test]
+    fn wide_integers() {
+        let value: Value = serde_json::from_str("[18446744073709551615, -9223372036854775808]").unwrap();
+        assert_eq!(value, Value::Array(vec![Value::Integer(u64::MAX.into()), Value::Integer(i64::MIN.into())]));
+        assert_eq!(serde_json::to_string(&value).unwrap(), "[18446744073709551615,-9223372036854775808]");
+    }
+
+    #[test]
+    fn scalar_keys_and_datetimes() {
+        let value: Value = serde_yaml::from_str("1: a\ntrue: b\n").unwrap();
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"1":"a","true":"b"}"#);
+        assert!(serde_yaml::from_str::<Value>("[1]: a\n").is_err(), "complex keys");
+
+        let value: Value = toml::from_str("when = 1979-05-27T07:32:00Z\n").unwrap();
+        assert_eq!(serde_json::to_string(&value).unwrap(), r#"{"when":"1979-05-27T07:32:00Z"}"#);
+    }
+}
@@ -0,0 +1,361 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{Context, Result};
+use reqwest::header::{
+    HeaderMap, HeaderName, HeaderValue, AGE, CACHE_CONTROL, DATE, ETAG, EXPIRES,
+    IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED, VARY,
+};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// statuses that may be stored without
+/// explicit permission (RFC 9110 15.1).
+const CACHEABLE_STATUSES: [u16; 11] = [200, 203, 204, 300, 301, 308, 404, 405, 410, 414, 501];
+
+/// on-disk http cache. each method+url gets an index file
+/// listing the stored variants (one per combination of
+/// Vary'd request header values), and each variant keeps
+/// its body in a separate file.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+/// what identifies a request to the cache.
+pub struct CacheKey {
+    method: Method,
+    url: String,
+    headers: HeaderMap,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheEntry {
+    status: u16,
+    headers: Vec<(String, String)>,
+    /// request header values the response varies on
+    vary: Vec<(String, Option<String>)>,
+    /// seconds since the unix epoch
+    stored_at: u64,
+    body_file: String,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl Cache {
+    pub fn new(dir: PathBuf) -> Result<Self> {
+        fs::create_dir_all(&dir)
+            .with_context(|| format!("creating cache dir {}", dir.display()))?;
+        Ok(Cache { dir })
+    }
+
+    /// $XDG_CACHE_HOME/rq, falling back to ~/.cache/rq.
+    pub fn default_dir() -> Option<PathBuf> {
+        std::env::var_os("XDG_CACHE_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+            .map(|dir| dir.join("rq"))
+    }
+
+    pub fn lookup(&self, key: &CacheKey) -> Result<Option<CacheEntry>> {
+        let Some(mut entry) = self.read_index(key)?
+            .into_iter()
+            .find(|entry| entry.matches(key)) else {
+            return Ok(None);
+        };
+        match fs::read(self.dir.join(&entry.body_file)) {
+            Ok(body) => entry.body = body,
+            // index without a body, treat as a miss
+            Err(_) => return Ok(None),
+        }
+        Ok(Some(entry))
+    }
+
+    /// stores the response if it's allowed to be cached.
+    /// returns whether it was stored.
+    pub fn store(&self, key: &CacheKey, status: StatusCode, headers: &HeaderMap, body: &[u8]) -> Result<bool> {
+        if !is_storable(key, status, headers) {
+            return Ok(false);
+        }
+        let vary = vary_names(headers)
+            .into_iter()
+            .map(|name| {
+                let value = key.headers.get(&name).map(|v| String::from_utf8_lossy(v.as_bytes()).into_owned());
+                (name, value)
+            })
+            .collect::<Vec<_>>();
+        let body_file = format!("{}.body", hash(&[key.index_name().as_str(), &format!("{vary:?}")]));
+        let entry = CacheEntry {
+            status: status.as_u16(),
+            headers: headers_to_vec(headers),
+            vary,
+            stored_at: now(),
+            body_file,
+            body: Vec::new(),
+        };
+        fs::write(self.dir.join(&entry.body_file), body)
+            .with_context(|| "writing cached body")?;
+        self.write_entry(key, entry)?;
+        Ok(true)
+    }
+
+    /// updates a stored entry with the headers from a 304
+    /// and resets its age.
+    pub fn refresh(&self, key: &CacheKey, mut entry: CacheEntry, headers: &HeaderMap) -> Result<CacheEntry> {
+        let mut merged = entry.headers();
+        for name in headers.keys() {
+            merged.remove(name);
+        }
+        for (name, value) in headers {
+            merged.append(name, value.clone());
+        }
+        entry.headers = headers_to_vec(&merged);
+        entry.stored_at = now();
+        self.write_entry(key, entry.clone())?;
+        Ok(entry)
+    }
+
+    fn write_entry(&self, key: &CacheKey, entry: CacheEntry) -> Result<()> {
+        let mut index = self.read_index(key)?;
+        index.retain(|e| e.body_file != entry.body_file);
+        index.push(entry);
+        let path = self.index_path(key);
+        let json = serde_json::to_vec(&index)?;
+        fs::write(&path, json)
+            .with_context(|| format!("writing cache index {}", path.display()))
+    }
+
+    fn read_index(&self, key: &CacheKey) -> Result<Vec<CacheEntry>> {
+        let path = self.index_path(key);
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(&path)?;
+        // a corrupt index is just a miss
+        Ok(serde_json::from_slice(&data).unwrap_or_default())
+    }
+
+    fn index_path(&self, key: &CacheKey) -> PathBuf {
+        self.dir.join(format!("{}.json", hash(&[key.index_name().as_str()])))
+    }
+}
+
+impl CacheKey {
+    pub fn new(method: &Method, url: &str, headers: &HeaderMap) -> Self {
+        CacheKey {
+            method: method.clone(),
+            url: url.to_string(),
+            headers: headers.clone(),
+        }
+    }
+
+    pub fn is_cacheable(&self) -> bool {
+        self.method == Method::GET
+    }
+
+    fn index_name(&self) -> String {
+        format!("{} {}", self.method, self.url)
+    }
+}
+
+impl CacheEntry {
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::OK)
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (HeaderName::from_bytes(name.as_bytes()), HeaderValue::from_str(value)) {
+                headers.append(name, value);
+            }
+        }
+        headers
+    }
+
+    /// whether the entry can be used without revalidating.
+    pub fn is_fresh(&self) -> bool {
+        let headers = self.headers();
+        let Some(lifetime) = freshness_lifetime(&headers) else {
+            return false;
+        };
+        let age = header_u64(&headers, &AGE).unwrap_or(0) + now().saturating_sub(self.stored_at);
+        age < lifetime.as_secs()
+    }
+
+    /// adds If-None-Match/If-Modified-Since so the server
+    /// can answer 304 if the entry is still good.
+    pub fn add_validators(&self, req_headers: &mut HeaderMap) {
+        let headers = self.headers();
+        if let Some(etag) = headers.get(ETAG) {
+            req_headers.insert(IF_NONE_MATCH, etag.clone());
+        }
+        if let Some(last_modified) = headers.get(LAST_MODIFIED) {
+            req_headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+        }
+    }
+
+    fn matches(&self, key: &CacheKey) -> bool {
+        self.vary.iter().all(|(name, value)| {
+            let current = key.headers.get(name.as_str()).map(|v| String::from_utf8_lossy(v.as_bytes()));
+            current.as_deref() == value.as_deref()
+        })
+    }
+}
+
+fn is_storable(key: &CacheKey, status: StatusCode, headers: &HeaderMap) -> bool {
+    let directives = cache_control(headers);
+    key.is_cacheable()
+        && CACHEABLE_STATUSES.contains(&status.as_u16())
+        && !directives.iter().any(|(d, _)| d == "no-store")
+        && !vary_names(headers).iter().any(|name| name == "*")
+        // nothing to gain from storing it otherwise
+        && (freshness_lifetime(headers).is_some() || headers.contains_key(ETAG) || headers.contains_key(LAST_MODIFIED))
+}
+
+/// max-age, falling back to Expires - Date.
+/// no-cache means it always has to be revalidated.
+fn freshness_lifetime(headers: &HeaderMap) -> Option<Duration> {
+    let directives = cache_control(headers);
+    if directives.iter().any(|(d, _)| d == "no-cache") {
+        return Some(Duration::ZERO);
+    }
+    if let Some(max_age) = directives.iter()
+        .find(|(d, _)| d == "max-age")
+        .and_then(|(_, v)| v.as_deref()?.parse().ok()) {
+        return Some(Duration::from_secs(max_age));
+    }
+    let expires = header_date(headers, &EXPIRES);
+    if headers.contains_key(EXPIRES) && expires.is_none() {
+        // invalid dates mean already expired
+        return Some(Duration::ZERO);
+    }
+    let date = header_date(headers, &DATE).unwrap_or_else(SystemTime::now);
+    expires.map(|expires| expires.duration_since(date).unwrap_or(Duration::ZERO))
+}
+
+fn cache_control(headers: &HeaderMap) -> Vec<(String, Option<String>)> {
+    headers.get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|directive| {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name, Some(value.trim().trim_matches('"').to_string())),
+                None => (directive, None),
+            };
+            (name.trim().to_lowercase(), value)
+        })
+        .collect()
+}
+
+fn vary_names(headers: &HeaderMap) -> Vec<String> {
+    headers.get_all(VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_lowercase())
+        .filter(|name| !name.is_empty())
+        .collect()
+}
+
+fn header_date(headers: &HeaderMap, name: &HeaderName) -> Option<SystemTime> {
+    httpdate::parse_http_date(headers.get(name)?.to_str().ok()?).ok()
+}
+
+fn header_u64(headers: &HeaderMap, name: &HeaderName) -> Option<u64> {
+    headers.get(name)?.to_str().ok()?.trim().parse().ok()
+}
+
+fn headers_to_vec(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers.iter()
+        .map(|(name, value)| (name.to_string(), String::from_utf8_lossy(value.as_bytes()).into_owned()))
+        .collect()
+}
+
+fn hash(parts: &[&str]) -> String {
+    let mut hasher = Sha256::new();
+    for part in parts {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, HeaderValue::from_static(value));
+        }
+        headers
+    }
+
+    fn temp_cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("rq-cache-test-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Cache::new(dir).unwrap()
+    }
+
+    #[test]
+    fn freshness() {
+        assert_eq!(freshness_lifetime(&headers(&[("cache-control", "public, max-age=60")])), Some(Duration::from_secs(60)));
+        assert_eq!(freshness_lifetime(&headers(&[("cache-control", "no-cache, max-age=60")])), Some(Duration::ZERO), "no-cache wins");
+        assert_eq!(
+            freshness_lifetime(&headers(&[("date", "Sun, 06 Nov 1994 08:49:37 GMT"), ("expires", "Sun, 06 Nov 1994 08:50:37 GMT")])),
+            Some(Duration::from_secs(60)),
+            "expires - date",
+        );
+        assert_eq!(freshness_lifetime(&headers(&[("expires", "0")])), Some(Duration::ZERO), "invalid expires");
+        assert_eq!(freshness_lifetime(&headers(&[])), None);
+    }
+
+    #[test]
+    fn store_and_lookup() {
+        let cache = temp_cache("store");
+        let key = CacheKey::new(&Method::GET, "https://example.com/a", &headers(&[("accept", "application/json")]));
+        let res = headers(&[("cache-control", "max-age=60"), ("etag", "\"abc\""), ("vary", "Accept")]);
+        assert!(cache.store(&key, StatusCode::OK, &res, b"hello").unwrap());
+
+        let entry = cache.lookup(&key).unwrap().unwrap();
+        assert_eq!(entry.body, b"hello");
+        assert!(entry.is_fresh());
+        let mut req_headers = HeaderMap::new();
+        entry.add_validators(&mut req_headers);
+        assert_eq!(req_headers.get(IF_NONE_MATCH).unwrap(), "\"abc\"");
+
+        let other = CacheKey::new(&Method::GET, "https://example.com/a", &headers(&[("accept", "text/html")]));
+        assert!(cache.lookup(&other).unwrap().is_none(), "vary mismatch");
+        let post = CacheKey::new(&Method::POST, "https://example.com/a", &HeaderMap::new());
+        assert!(!cache.store(&post, StatusCode::OK, &res, b"hello").unwrap(), "only GET is stored");
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+
+    #[test]
+    fn refresh_on_not_modified() {
+        let cache = temp_cache("refresh");
+        let key = CacheKey::new(&Method::GET, "https://example.com/b", &HeaderMap::new());
+        let res = headers(&[("cache-control", "no-cache"), ("etag", "\"v1\"")]);
+        assert!(cache.store(&key, StatusCode::OK, &res, b"body").unwrap());
+        let entry = cache.lookup(&key).unwrap().unwrap();
+        assert!(!entry.is_fresh());
+
+        let entry = cache.refresh(&key, entry, &headers(&[("cache-control", "max-age=30")])).unwrap();
+        assert!(entry.is_fresh());
+        assert_eq!(entry.body, b"body");
+        assert_eq!(cache.lookup(&key).unwrap().unwrap().headers().get(ETAG).unwrap(), "\"v1\"");
+        assert!(!cache.store(&key, StatusCode::OK, &headers(&[("cache-control", "no-store")]), b"x").unwrap());
+        fs::remove_dir_all(&cache.dir).unwrap();
+    }
+}
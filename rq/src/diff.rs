@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use similar::TextDiff;
+
+use crate::response::header_map;
+use crate::ResponseSummary;
+
+// what to compare between two responses.
+#[derive(Debug, Default, clap::Args)]
+pub struct DiffArgs {
+    /// header(s) to compare, in addition
+    /// to content-type.
+    #[clap(long = "diff-header")]
+    diff_headers: Vec<String>,
+
+    /// json pointer(s) to leave out of the
+    /// body comparison, e.g. /meta/timestamp
+    #[clap(long)]
+    ignore: Vec<String>,
+}
+
+/// the parts of a response that get compared.
+/// json bodies are stored as json so that
+/// snapshots stay readable.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    status: u16,
+    headers: BTreeMap<String, Value>,
+    body: Value,
+}
+
+#[derive(Debug, PartialEq)]
+enum Change {
+    Added { path: String, value: Value },
+    Removed { path: String, value: Value },
+    Changed { path: String, from: Value, to: Value },
+}
+
+impl Snapshot {
+    pub fn from_response(res: &ResponseSummary) -> Self {
+        let headers = header_map(res.headers());
+        let body = serde_json::from_slice(res.body())
+            .unwrap_or_else(|_| Value::String(res.text()));
+        Snapshot { status: res.status().as_u16(), headers, body }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read snapshot {}", path.display()))?;
+        serde_json::from_str(&data)
+            .with_context(|| format!("bad snapshot {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_string_pretty(self)?;
+        fs::write(path, data)
+            .with_context(|| format!("failed to write snapshot {}", path.display()))
+    }
+}
+
+/// prints the differences between two snapshots and
+/// errors if there are any, so scripts can check the
+/// exit code.
+pub fn report(a: &Snapshot, a_label: &str, b: &Snapshot, b_label: &str, args: &DiffArgs) -> Result<()> {
+    println!("--- {a_label}");
+    println!("+++ {b_label}");
+    let mut differs = false;
+
+    if a.status != b.status {
+        differs = true;
+        println!("status: {} -> {}", a.status, b.status);
+    }
+
+    let names = std::iter::once("content-type".to_string())
+        .chain(args.diff_headers.iter().map(|h| h.to_lowercase()));
+    for name in names {
+        let (from, to) = (a.headers.get(&name), b.headers.get(&name));
+        if from != to {
+            differs = true;
+            println!("header {name}: {} -> {}", display_header(from), display_header(to));
+        }
+    }
+
+    match (&a.body, &b.body) {
+        (Value::String(from), Value::String(to)) => {
+            if from != to {
+                differs = true;
+                println!("body:");
+                print!("{}", TextDiff::from_lines(from, to).unified_diff().context_radius(3));
+            }
+        }
+        (from, to) => {
+            let changes: Vec<_> = diff_json(from, to)
+                .into_iter()
+                .filter(|c| !args.ignore.iter().any(|ignored| c.is_under(ignored)))
+                .collect();
+            if !changes.is_empty() {
+                differs = true;
+                println!("body:");
+                for change in changes {
+                    println!("  {change}");
+                }
+            }
+        }
+    }
+
+    if differs {
+        return Err(anyhow!("responses differ"));
+    }
+    println!("no differences");
+    Ok(())
+}
+
+fn display_header(value: Option<&Value>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_else(|| "(none)".to_string())
+}
+
+/// key-order independent structural diff,
+/// with paths as json pointers.
+fn diff_json(a: &Value, b: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at("", a, b, &mut changes);
+    changes
+}
+
+fn diff_at(path: &str, a: &Value, b: &Value, changes: &mut Vec<Change>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            for (key, a_value) in a {
+                let child = format!("{path}/{}", escape(key));
+                match b.get(key) {
+                    Some(b_value) => diff_at(&child, a_value, b_value, changes),
+                    None => changes.push(Change::Removed { path: child, value: a_value.clone() }),
+                }
+            }
+            for (key, b_value) in b {
+                if !a.contains_key(key) {
+                    let path = format!("{path}/{}", escape(key));
+                    changes.push(Change::Added { path, value: b_value.clone() });
+                }
+            }
+        }
+        (Value::Array(a), Value::Array(b)) => {
+            for (i, a_value) in a.iter().enumerate() {
+                let child = format!("{path}/{i}");
+                match b.get(i) {
+                    Some(b_value) => diff_at(&child, a_value, b_value, changes),
+                    None => changes.push(Change::Removed { path: child, value: a_value.clone() }),
+                }
+            }
+            for (i, b_value) in b.iter().enumerate().skip(a.len()) {
+                changes.push(Change::Added { path: format!("{path}/{i}"), value: b_value.clone() });
+            }
+        }
+        (a, b) if a != b => changes.push(Change::Changed {
+            path: path.to_string(),
+            from: a.clone(),
+            to: b.clone(),
+        }),
+        _ => {}
+    }
+}
+
+/// json pointer escaping (RFC 6901).
+fn escape(key: &str) -> String {
+    key.replace('~', "~0").replace('/', "~1")
+}
+
+impl Change {
+    fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } | Change::Changed { path, .. } => path,
+        }
+    }
+
+    fn is_under(&self, pointer: &str) -> bool {
+        let path = self.path();
+        let pointer = pointer.trim_end_matches('/');
+        path == pointer || path.starts_with(&format!("{pointer}/"))
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let root = |path: &str| if path.is_empty() { "/".to_string() } else { path.to_string() };
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {value}", root(path)),
+            Change::Removed { path, value } => write!(f, "- {}: {value}", root(path)),
+            Change::Changed { path, from, to } => write!(f, "~ {}: {from} -> {to}", root(path)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_json_success() {
+        let a = json!({"name": "a", "tags": ["x", "y"], "meta": {"id": 1, "a/b": true}});
+        let b = json!({"meta": {"id": 2, "a/b": true}, "tags": ["x"], "name": "a", "new": null});
+        let changes = diff_json(&a, &b);
+        assert_eq!(changes, vec![
+            Change::Changed { path: "/meta/id".to_string(), from: json!(1), to: json!(2) },
+            Change::Removed { path: "/tags/1".to_string(), value: json!("y") },
+            Change::Added { path: "/new".to_string(), value: json!(null) },
+        ]);
+        assert!(diff_json(&a, &a.clone()).is_empty(), "no changes");
+        assert_eq!(diff_json(&json!(1), &json!("1")).len(), 1, "type change at root");
+    }
+
+    #[test]
+    fn ignored_paths() {
+        let change = Change::Changed { path: "/meta/timestamp".to_string(), from: json!(1), to: json!(2) };
+        assert!(change.is_under("/meta"));
+        assert!(change.is_under("/meta/timestamp"));
+        assert!(!change.is_under("/met"));
+        assert_eq!(escape("a/b~c"), "a~1b~0c");
+    }
+}
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use reqwest::blocking::Request;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::response::{header_map, Redirect};
+use crate::ResponseSummary;
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// one pretty-printed document
+    Json,
+    /// one document per line
+    Ndjson,
+}
+
+/// a request and its response as one document,
+/// for logging or piping into other tools.
+#[derive(Debug, Serialize)]
+pub struct Exchange {
+    request: RequestRecord,
+    response: Option<ResponseRecord>,
+    redirects: Vec<Redirect>,
+    timings: Option<Timings>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RequestRecord {
+    method: String,
+    url: String,
+    version: String,
+    headers: BTreeMap<String, Value>,
+    body: Option<BodyRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct ResponseRecord {
+    version: Option<String>,
+    status: u16,
+    reason: Option<&'static str>,
+    cached: bool,
+    headers: BTreeMap<String, Value>,
+    body: Option<BodyRecord>,
+}
+
+#[derive(Debug, Serialize)]
+struct Timings {
+    headers_ms: f64,
+    total_ms: f64,
+}
+
+/// text bodies as-is, anything else base64 encoded.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum BodyRecord {
+    Text(String),
+    Base64(String),
+}
+
+impl RequestRecord {
+    /// streamed bodies (files, multipart)
+    /// are left out.
+    pub fn new(req: &Request) -> Self {
+        let body = req.body()
+            .and_then(|b| b.as_bytes())
+            .map(|bytes| match std::str::from_utf8(bytes) {
+                Ok(text) => BodyRecord::Text(text.to_string()),
+                Err(_) => BodyRecord::Base64(STANDARD.encode(bytes)),
+            });
+        RequestRecord {
+            method: req.method().to_string(),
+            url: req.url().to_string(),
+            version: format!("{:?}", req.version()),
+            headers: header_map(req.headers()),
+            body,
+        }
+    }
+}
+
+impl Exchange {
+    /// an exchange that never got a response, e.g. --debug.
+    pub fn request_only(request: RequestRecord) -> Self {
+        Exchange { request, response: None, redirects: Vec::new(), timings: None }
+    }
+
+    /// include_body is false when the body was
+    /// written somewhere else (--output).
+    pub fn new(request: RequestRecord, res: &ResponseSummary, include_body: bool) -> Self {
+        let body = include_body.then(|| {
+            if res.is_binary() {
+                BodyRecord::Base64(STANDARD.encode(res.body()))
+            } else {
+                BodyRecord::Text(res.text())
+            }
+        });
+        let response = ResponseRecord {
+            version: res.version().map(|v| format!("{v:?}")),
+            status: res.status().as_u16(),
+            reason: res.status().canonical_reason(),
+            cached: res.is_cached(),
+            headers: header_map(res.headers()),
+            body,
+        };
+        let timings = Timings {
+            headers_ms: res.headers_elapsed().as_secs_f64() * 1000.0,
+            total_ms: res.elapsed().as_secs_f64() * 1000.0,
+        };
+        Exchange {
+            request,
+            response: Some(response),
+            redirects: res.redirects().to_vec(),
+            timings: Some(timings),
+        }
+    }
+
+    pub fn write(&self, format: OutputFormat, w: &mut impl Write) -> Result<()> {
+        match format {
+            OutputFormat::Json => serde_json::to_writer_pretty(&mut *w, self)?,
+            OutputFormat::Ndjson => serde_json::to_writer(&mut *w, self)?,
+        }
+        writeln!(w)?;
+        w.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use reqwest::{StatusCode, Version};
+    use serde_json::json;
+
+    #[test]
+    fn exchange_document() {
+        let req = reqwest::blocking::Client::new()
+            .post("http://localhost/users")
+            .header("x-trace", "1")
+            .body("name=a")
+            .build()
+            .unwrap();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        let res = ResponseSummary {
+            version: Some(Version::HTTP_11),
+            status: StatusCode::CREATED,
+            headers,
+            body: b"\x89PNG".to_vec(),
+            redirects: vec![Redirect {
+                url: "http://localhost/old".to_string(),
+                status: 301,
+                location: "http://localhost/users".to_string(),
+            }],
+            ..Default::default()
+        };
+
+        let exchange = Exchange::new(RequestRecord::new(&req), &res, true);
+        let mut out = Vec::new();
+        exchange.write(OutputFormat::Ndjson, &mut out).unwrap();
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 1, "one line per exchange");
+
+        let doc: Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(doc["request"]["method"], "POST");
+        assert_eq!(doc["request"]["headers"]["x-trace"], "1");
+        assert_eq!(doc["request"]["body"], json!({"text": "name=a"}));
+        assert_eq!(doc["response"]["status"], 201);
+        assert_eq!(doc["response"]["version"], "HTTP/1.1");
+        assert_eq!(doc["response"]["body"], json!({"base64": "iVBORw=="}), "binary body");
+        assert_eq!(doc["redirects"][0]["status"], 301);
+
+        let without_body = Exchange::new(RequestRecord::new(&req), &res, false);
+        assert!(without_body.response.unwrap().body.is_none());
+
+        // a 304 answered from the cache still has a version
+        let revalidated = ResponseSummary { version: Some(Version::HTTP_11), cached: true, ..Default::default() };
+        assert!(Exchange::new(RequestRecord::new(&req), &revalidated, false).response.unwrap().cached);
+    }
+
+    #[test]
+    fn records_the_default_headers() {
+        let spec = crate::spec::RequestSpec::new("http://localhost/users").user_agent("rq-test");
+        let record = RequestRecord::new(spec.prepare().unwrap().request());
+        assert_eq!(record.headers["user-agent"], "rq-test");
+        assert_eq!(record.headers["accept"], "*/*");
+    }
+}
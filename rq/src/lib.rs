@@ -1,26 +1,133 @@
-use core::str;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::{PathBuf, Path};
-use std::str::FromStr;
-use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::io::{self, IsTerminal};
 
 use anyhow::{anyhow, Context, Result};
-use clap::Parser;
-use reqwest::header::{HeaderMap, HeaderName, HeaderValue, COOKIE};
-use reqwest::{
-    Method,
-    blocking::{Request, Client, multipart},
-};
-use url::Url;
+use clap::{Parser, Subcommand};
+use reqwest::Method;
 use serde_json::Value;
 
-const USER_AGENT_DEFAULT: &str = "github.com/davemolk/rusty-bits/rq";
+mod cache;
+mod diff;
+mod exchange;
+mod netrc;
+mod openapi;
+mod response;
+mod shell;
+mod spec;
+mod upload;
+
+use diff::{DiffArgs, Snapshot};
+use exchange::{Exchange, OutputFormat, RequestRecord};
+use openapi::OpenApi;
+use spec::split_user_pw;
+
+pub use response::{Redirect, ResponseSummary};
+pub use spec::{Auth, Body, PreparedRequest, RequestSpec};
+
+/// file bodies at least this big get a
+/// progress indicator when stderr is a terminal.
+const PROGRESS_MIN_LEN: u64 = 1024 * 1024;
 
 #[derive(Debug, Parser, Default)]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
 pub struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// URL to request
     #[clap(required=true)]
-    url: String,
+    url: Option<String>,
+
+    #[command(flatten)]
+    request: RequestArgs,
+
+    /// compare the response against a snapshot
+    /// saved with --save-snapshot
+    #[clap(long)]
+    compare_to: Option<PathBuf>,
+
+    /// save the response status, headers
+    /// and body to a snapshot file
+    #[clap(long)]
+    save_snapshot: Option<PathBuf>,
+
+    #[command(flatten)]
+    diff: DiffArgs,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// make the same request to two urls
+    /// and diff the responses
+    Diff {
+        url_a: String,
+
+        url_b: String,
+
+        #[command(flatten)]
+        request: RequestArgs,
+
+        #[command(flatten)]
+        diff: DiffArgs,
+    },
+
+    /// call an operation from an OpenAPI spec and
+    /// check the response against it
+    Api {
+        /// OpenAPI 3 spec, as yaml or json
+        #[clap(long)]
+        spec: PathBuf,
+
+        /// operationId to call
+        operation_id: String,
+
+        /// parameters as name=value. names that aren't
+        /// declared path/query/header/cookie params go in
+        /// a json body; use name:=value for raw json and
+        /// name==value to force a query param.
+        params: Vec<String>,
+
+        #[command(flatten)]
+        request: RequestArgs,
+    },
+
+    /// interactive shell against a base url, e.g.
+    /// `get /users` or `post /users name=x`
+    Shell {
+        base_url: String,
+
+        /// run the commands in this script
+        /// instead of reading from the terminal
+        #[clap(long)]
+        script: Option<PathBuf>,
+
+        #[command(flatten)]
+        request: RequestArgs,
+    },
+}
+
+// options shared by every request rq makes.
+#[derive(Debug, clap::Args, Default)]
+struct RequestArgs {
+    /// base url that relative urls (e.g. /users)
+    /// are resolved against.
+    #[clap(long, env = "RQ_BASE")]
+    base: Option<String>,
+
+    /// query parameter(s) to append, percent-encoded.
+    /// 
+    /// -q search="two words" -q page=2
+    #[clap(short, long, value_parser = parse_key_value)]
+    query: Vec<(String, String)>,
+
+    /// value(s) for :name or {name} placeholders
+    /// in the url.
+    /// 
+    /// rq /users/:id --path-param id=42
+    #[clap(long, value_parser = parse_key_value)]
+    path_param: Vec<(String, String)>,
 
     /// defaults to GET if a value is not supplied
     #[clap(short, long, default_value = "GET", value_parser = parse_method)]
@@ -36,14 +143,30 @@ pub struct Args {
     bearer: Option<String>,
 
     /// data for request body.
-    /// preface a file_path with @.
+    /// preface a file_path with @, or use @-
+    /// to stream from stdin. Content-Type is
+    /// guessed from the file extension unless
+    /// set with -H.
     /// 
     /// -d "my string"
     /// -d '{"my": "json"}'
-    /// -d @my_file
+    /// -d @my_file.json
+    /// -d @-
     #[clap(short, long)]
     data: Option<String>,
 
+    /// send file bodies with chunked transfer
+    /// encoding rather than Content-Length.
+    /// stdin bodies are always chunked.
+    #[clap(long)]
+    chunked: bool,
+
+    /// show upload progress on stderr. on by
+    /// default for files over 1 MiB when stderr
+    /// is a terminal.
+    #[clap(long)]
+    progress: bool,
+
     /// send a multipart/form-data body
     #[clap(long)]
     form: Option<String>,
@@ -62,12 +185,21 @@ pub struct Args {
     #[clap(short, long="timeout")]
     timeout_seconds: Option<u64>,
 
-    /// header(s) to include.
+    /// header(s) to include, as Name:value
+    /// or Name=value. repeated headers are all
+    /// sent. leave the value empty to unset a
+    /// header (e.g. the default user-agent). accept
+    /// is sent empty instead, as reqwest adds */*
+    /// to a request without one.
     /// preface a file_path with @
     /// to set headers from a json_file.
-    /// e.g. { "Accept-Language": "en-US" }
+    /// e.g. { "Accept-Language": "en-US", "X-Tag": ["a", "b"] }
+    /// use null to unset a header from a file
+    /// (accept too is sent empty).
     /// 
     /// -H Accept-Language=en-US
+    /// -H "Authorization: Basic abc=="
+    /// -H User-Agent:
     #[clap(short='H', long)]
     headers: Option<Vec<String>>,
 
@@ -81,13 +213,37 @@ pub struct Args {
     #[clap(short, long)]
     cookies: Option<String>,
 
-    /// proxy to use
+    /// proxy to use (http, https, socks5 or socks5h).
+    /// credentials can be included in the url.
+    /// if not set, HTTP_PROXY, HTTPS_PROXY and ALL_PROXY
+    /// are read from the environment.
     #[clap(short, long)]
     proxy: Option<String>,
 
+    /// proxy auth
+    /// (formatted as user:pw)
+    #[clap(long)]
+    proxy_user: Option<String>,
+
+    /// comma-separated hosts that bypass the proxy.
+    /// overrides NO_PROXY. use "*" to disable
+    /// proxies entirely.
+    #[clap(long)]
+    noproxy: Option<String>,
+
+    /// read credentials for the request host
+    /// from ~/.netrc
+    #[clap(long)]
+    netrc: bool,
+
+    /// read credentials from the provided
+    /// netrc file (implies --netrc)
+    #[clap(long)]
+    netrc_file: Option<PathBuf>,
+
     /// don't follow redirects
     #[clap(long="no-redirects")]
-    redirects: bool,
+    no_redirects: bool,
 
     /// only use HTTP/2
     #[clap(long)]
@@ -103,167 +259,245 @@ pub struct Args {
     #[clap(long)]
     user_agent: Option<String>,
 
-    /// download file to provided path.
+    /// write the response body to the provided
+    /// path, streaming it rather than holding it
+    /// in memory.
+    #[clap(short, long, alias = "download")]
+    output: Option<PathBuf>,
+
+    /// write the response body to stdout
+    /// byte for byte, without decoding it.
+    #[clap(long)]
+    raw: bool,
+
+    /// print binary bodies to the terminal anyway.
     #[clap(long)]
-    download: Option<String>,
+    force_binary: bool,
+
+    /// decode the body with this charset instead of
+    /// the one from Content-Type, e.g. iso-8859-1
+    #[clap(long)]
+    charset: Option<String>,
 
     /// pretty-print json file.
     #[clap(long="pp")]
     pretty_print: bool,
-}
 
-pub fn run(mut args: Args) -> Result<()> {
-    let client = build_client(&mut args)?;
-    let req = build_request(&mut args, &client)?;    
+    /// print the whole exchange (request, response,
+    /// timings and redirects) as json instead, one
+    /// document per request. binary bodies are base64
+    /// encoded. replaces the --verbose output.
+    #[clap(long, value_enum)]
+    output_format: Option<OutputFormat>,
+
+    /// cache GET responses and reuse them while
+    /// fresh (Cache-Control/Expires). stale responses
+    /// are revalidated with If-None-Match and
+    /// If-Modified-Since.
+    #[clap(long)]
+    cache: bool,
 
-    if args.verbose || args.debug {
-        println!("{:?}", req.version());
-        println!("{:?}", req.url().as_str());
-        println!("{:?}", req.method());
-        for (h, v) in req.headers() {
-            println!("{:?}: {:?}", h, v);
-        };
-        if let Some(t) = req.timeout() {
-            println!("timeout: {:?}", t)
+    /// directory for cached responses (implies --cache).
+    /// defaults to $XDG_CACHE_HOME/rq or ~/.cache/rq.
+    #[clap(long)]
+    cache_dir: Option<PathBuf>,
+}
+
+pub fn run(args: Args) -> Result<()> {
+    match &args.command {
+        Some(Command::Diff { url_a, url_b, request, diff }) => {
+            let a = Snapshot::from_response(&request.to_spec(url_a)?.execute()?);
+            let b = Snapshot::from_response(&request.to_spec(url_b)?.execute()?);
+            return diff::report(&a, url_a, &b, url_b, diff);
+        }
+        Some(Command::Api { spec, operation_id, params, request }) => {
+            return run_api(spec, operation_id, params, request);
         }
-        println!();
+        Some(Command::Shell { base_url, script, request }) => {
+            let mut template = request.to_spec("")?;
+            template.base = Some(base_url.clone());
+            let mut session = shell::Session::new(template)?;
+            return match script {
+                Some(script) => session.source(script),
+                None => session.run(),
+            };
+        }
+        None => {}
     }
 
-    if args.debug {
+    let url = args.url.ok_or_else(|| anyhow!("a url is required"))?;
+    let request = &args.request;
+    let spec = request.to_spec(&url)?;
+    let Some(data) = send(request, &spec)? else {
         return Ok(());
-    }
-
-    let mut data = client.execute(req)?;
+    };
 
-    if !data.status().is_success() {
-        eprintln!("status: {:?}",data.status().canonical_reason())
+    if let Some(path) = &args.save_snapshot {
+        Snapshot::from_response(&data).save(path)?;
     }
 
-    if args.verbose {
-        println!("{:?} {:?} {:?}", data.version(), data.status(), data.status().canonical_reason().unwrap_or_default());
-        for (h, v) in data.headers() {
-            println!("{:?}: {:?}", h, v)
-        }
-        println!();
+    if let Some(path) = &args.compare_to {
+        let expected = Snapshot::load(path)?;
+        let label = path.display().to_string();
+        return diff::report(&expected, &label, &Snapshot::from_response(&data), &url, &args.diff);
     }
 
-    if let Some(download_path) = args.download {
-        let mut file = fs::File::create(&download_path)?;
-        println!("downloading file...");
-        file.write_all(&mut data.bytes()?)?;
+    print_response(request, &data)
+}
+
+/// builds the request from an openapi operation, then checks
+/// the response against what the spec declares.
+fn run_api(spec_path: &Path, operation_id: &str, params: &[String], request: &RequestArgs) -> Result<()> {
+    let api = OpenApi::load(spec_path)?;
+    let op = api.operation(operation_id)?;
+    let mut spec = request.to_spec("")?;
+    op.apply(&mut spec, params)?;
+    let Some(data) = send(request, &spec)? else {
         return Ok(());
-    }
+    };
+    print_response(request, &data)?;
 
-    if args.pretty_print {
-        let json_res: Value = serde_json::from_reader(&mut data)?;
-        match serde_json::to_string_pretty(&json_res) {
-            Ok(pp) => println!("{pp}"),
-            // just print it
-            Err(_) => println!("{:?}", data.text()),
-        }
-    } else {
-        println!("{}", data.text()?);
+    let mismatches = op.validate(&data);
+    for mismatch in &mismatches {
+        eprintln!("spec mismatch: {mismatch}");
+    }
+    if !mismatches.is_empty() {
+        return Err(anyhow!("response does not match {operation_id} in {}", spec_path.display()));
     }
     Ok(())
 }
 
-fn build_client(args: &mut Args) -> Result<Client> {
-    let mut client = reqwest::blocking::ClientBuilder::new();
-
-    client = if let Some(ua) = &args.user_agent {
-        client.user_agent(ua)
-    } else {
-        client.user_agent(USER_AGENT_DEFAULT)
-    };
-    
-    if !args.redirects {
-        client = client.redirect(reqwest::redirect::Policy::none());
+/// sends the request, handling --debug, --verbose, --output
+/// and --output-format. returns None if there's nothing left
+/// to print.
+fn send(request: &RequestArgs, spec: &RequestSpec) -> Result<Option<ResponseSummary>> {
+    let prepared = spec.prepare()?;
+    let structured = request.output_format.map(|format| (format, RequestRecord::new(prepared.request())));
+    let verbose = request.verbose && structured.is_none();
+
+    if request.debug {
+        match structured {
+            Some((format, record)) => Exchange::request_only(record).write(format, &mut io::stdout())?,
+            None => prepared.print(),
+        }
+        return Ok(None);
     }
 
-    if args.http2 {
-        client = client.http2_prior_knowledge();
+    if verbose {
+        prepared.print();
     }
 
-    if let Some(proxy) = &args.proxy {
-        client = client.proxy(reqwest::Proxy::all(proxy).with_context(|| format!("invalid proxy: {}", proxy))?);
+    if let Some(output) = &request.output {
+        let mut file = fs::File::create(output)
+            .with_context(|| format!("failed to create {}", output.display()))?;
+        let data = prepared.send_to(&mut file)?;
+        if !data.status().is_success() {
+            eprintln!("status: {:?}",data.status().canonical_reason())
+        }
+        if verbose {
+            data.print_head();
+        }
+        if let Some((format, record)) = structured {
+            Exchange::new(record, &data, false).write(format, &mut io::stdout())?;
+        }
+        return Ok(None);
     }
 
-    let client = client.build().with_context(|| "building client")?;
-    Ok(client)
-}
-
-fn build_request(args: &mut Args, client: &Client) -> Result<Request> {    
-    let url = Url::parse(&args.url)
-        .with_context(|| format!("{} cannot be parsed as url", args.url))?;
-
-    let mut req_builder = match args.method {
-        Method::GET => client.get(url),
-        Method::HEAD => client.head(url),
-        Method::POST => client.post(url),
-        Method::PUT => client.put(url),
-        Method::PATCH => client.patch(url),
-        Method::DELETE => client.delete(url),
-        _ => client.get(url),
-    };
-
-    req_builder = req_builder
-        .headers(add_headers(&args.headers).with_context(|| "adding headers")?);
-        
-    if let Some(cookies) = &args.cookies {
-        let cookie_header = add_cookies(&cookies)?;
-        req_builder = req_builder.headers(cookie_header);
-    }   
-
-    if let Some(basic) = &args.basic {
-        let user_pw: Vec<&str> = basic.split(':').collect();
-        if user_pw.len() < 2 {
-            return Err(anyhow!("malformed basic auth: {}", basic));
-        }
-        req_builder = req_builder.basic_auth(user_pw[0], Some(user_pw[1]));
+    let mut data = prepared.send()?;
+    if let Some(charset) = &request.charset {
+        data.set_charset(charset)?;
     }
 
-    if let Some(bearer) = &args.bearer {
-        req_builder = req_builder.bearer_auth(bearer);
+    if !data.status().is_success() {
+        eprintln!("status: {:?}",data.status().canonical_reason())
     }
 
-    if let Some(t) = args.timeout_seconds {
-        req_builder = req_builder.timeout(std::time::Duration::from_secs(t));
+    if verbose {
+        data.print_head();
+    }
+    if let Some((format, record)) = structured {
+        Exchange::new(record, &data, true).write(format, &mut io::stdout())?;
     }
+    Ok(Some(data))
+}
 
-    if let Some(d) = &args.data {
-        if d.starts_with('@') {
-            let data_path = d.clone().split_off(1);
-            let file = std::fs::File::open(&data_path)
-                .with_context(|| format!("failed to open {data_path}"))?;
-            req_builder = req_builder.body(file);
-        } else {
-            req_builder = req_builder.body(d.clone());
-        }        
+/// prints the body, writing binary bodies as-is
+/// (but not to a terminal unless forced).
+fn print_response(request: &RequestArgs, data: &ResponseSummary) -> Result<()> {
+    if request.output_format.is_some() {
+        // already printed as part of the exchange
+        return Ok(());
     }
+    let binary = data.is_binary();
+    let mut stdout = io::stdout();
+    if binary && stdout.is_terminal() && !request.force_binary {
+        return Err(anyhow!("response looks binary, not printing it to the terminal. use --output or --force-binary"));
+    }
+    if request.raw || binary {
+        return data.write_body(&mut stdout);
+    }
+
+    data.print_body(request.pretty_print)
+}
 
-    // todo: allow files for form data too
-    if let Some(form_data) = &args.form {
-        let mut form = multipart::Form::new();
-        let form_json: Value = serde_json::from_str(form_data).
-            with_context(|| format!("bad form data: {}", form_data))?;
-        if let Some(object) = form_json.as_object() {
-            for (key, value) in object {
-                if let Some(form_value) = value.as_str() {
-                    // check if it's a file first, otherwise use as text
-                    let path = Path::new(form_value);
-                    if path.exists() {
-                        form = form.file(key.to_owned(), path)?;
-                    } else {
-                        form = form.text(key.to_owned(), form_value.to_owned());
-                    }
-                }
+impl RequestArgs {
+    fn to_spec(&self, url: &str) -> Result<RequestSpec> {
+        let auth = match (&self.bearer, &self.basic) {
+            (Some(token), _) => Some(Auth::Bearer { token: token.clone() }),
+            (None, Some(basic)) => {
+                let (user, pw) = split_user_pw(basic)
+                    .ok_or_else(|| anyhow!("malformed basic auth: {}", basic))?;
+                Some(Auth::Basic { user: user.to_string(), password: Some(pw.to_string()) })
             }
-        }
-        req_builder = req_builder.multipart(form);
-    }
+            (None, None) => None,
+        };
+
+        let body = if let Some(form_data) = &self.form {
+            let fields: BTreeMap<String, Value> = serde_json::from_str(form_data)
+                .with_context(|| format!("bad form data: {}", form_data))?;
+            let fields = fields.into_iter()
+                .filter_map(|(key, value)| Some((key, value.as_str()?.to_owned())))
+                .collect();
+            Some(Body::Form(fields))
+        } else {
+            self.data.as_ref().map(|d| match d.strip_prefix('@') {
+                Some("-") => Body::Stdin,
+                Some(data_path) => Body::File(PathBuf::from(data_path)),
+                None => Body::Text(d.clone()),
+            })
+        };
+
+        let progress = self.progress || match &body {
+            Some(Body::File(path)) => io::stderr().is_terminal()
+                && fs::metadata(path).is_ok_and(|m| m.len() >= PROGRESS_MIN_LEN),
+            _ => false,
+        };
 
-    let req = req_builder.build().with_context(|| "building request")?;
-    Ok(req)
+        Ok(RequestSpec {
+            url: url.to_string(),
+            base: self.base.clone(),
+            query: self.query.clone(),
+            path_params: self.path_param.iter().cloned().collect(),
+            method: self.method.clone(),
+            headers: self.headers.clone().unwrap_or_default(),
+            cookies: self.cookies.clone(),
+            auth,
+            body,
+            chunked: self.chunked,
+            progress,
+            timeout_seconds: self.timeout_seconds.map(|t| t as f64),
+            follow_redirects: !self.no_redirects,
+            http2: self.http2,
+            user_agent: self.user_agent.clone(),
+            proxy: self.proxy.clone(),
+            proxy_user: self.proxy_user.clone(),
+            noproxy: self.noproxy.clone(),
+            netrc: self.netrc,
+            netrc_file: self.netrc_file.clone(),
+            cache: self.cache,
+            cache_dir: self.cache_dir.clone(),
+        })
+    }
 }
 
 fn parse_method(method: &str) -> Result<Method> {
@@ -279,54 +513,8 @@ fn parse_method(method: &str) -> Result<Method> {
     Ok(method)
 }
 
-fn add_headers(headers_to_add: &Option<Vec<String>>) -> Result<HeaderMap> {
-    let mut header_map = HeaderMap::new();
-    if let Some(headers) = headers_to_add {
-        for header in headers {
-            if header.starts_with('@') {
-                // load from file
-                let path = PathBuf::from(header.clone().split_off(1));
-                add_headers_from_file(&mut header_map, path)?;
-            } else {
-                let header_pair: Vec<_> = header.split('=').collect();
-                if header_pair.len() != 2 {
-                    return Err(anyhow!("malformed header: {:?}", header_pair));
-                }
-                let header_key = HeaderName::from_str(header_pair[0])?;
-                let header_value = HeaderValue::from_str(header_pair[1])?;
-                header_map.insert(header_key, header_value);
-            }
-        }
-    }
-    Ok(header_map)
-}
-
-fn add_headers_from_file(headers: &mut HeaderMap, path: PathBuf) -> Result<()> {
-    let file = std::fs::File::open(&path)?;
-    let header_json: Value = serde_json::from_reader(file)
-        .with_context(|| format!("bad json format from header file: {:?}", path))?;
-    if let Some(json_obj) = header_json.as_object() {
-        for (key, value) in json_obj {
-            if let Some(v) = value.as_str() {
-                let header_key = HeaderName::from_str(key)?;
-                headers.insert(header_key, HeaderValue::from_str(v)?);
-            } else {
-                return Err(anyhow!("{value} is not a proper header value"))
-            }    
-        }
-    }
-    Ok(())
-}
-
-fn add_cookies(cookies: &str) -> Result<HeaderMap> {
-    let mut header_map = HeaderMap::new();
-    if cookies.starts_with('@') {
-        let (_, cookie_path) = cookies.split_at(1);
-        let path = PathBuf::from(cookie_path);
-        let file_cookies = fs::read_to_string(path)?;
-        header_map.insert(COOKIE, HeaderValue::from_str(&file_cookies)?);
-    } else {
-        header_map.insert(COOKIE, HeaderValue::from_str(&cookies)?);
-    }
-    Ok(header_map)
+fn parse_key_value(kv: &str) -> Result<(String, String)> {
+    let (key, value) = kv.split_once('=')
+        .ok_or_else(|| anyhow!("expected key=value, got {:?}", kv))?;
+    Ok((key.to_string(), value.to_string()))
 }
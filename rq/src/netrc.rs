@@ -0,0 +1,172 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+/// login and password for a single netrc entry.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Credentials {
+    pub login: String,
+    pub password: Option<String>,
+}
+
+/// parsed contents of a netrc file.
+/// entries are matched by host, falling back
+/// to the `default` entry if there is one.
+#[derive(Debug, Default)]
+pub struct Netrc {
+    machines: Vec<(String, Credentials)>,
+    default: Option<Credentials>,
+}
+
+impl Netrc {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)
+            .with_context(|| format!("failed to read netrc file {}", path.display()))?;
+        Self::parse(&contents)
+            .with_context(|| format!("bad netrc file {}", path.display()))
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        let mut netrc = Netrc::default();
+        // the entry currently being filled in: None for `default`
+        let mut current: Option<(Option<String>, Credentials)> = None;
+        let mut lines = contents.lines();
+
+        while let Some(line) = lines.next() {
+            let mut tokens = tokenize(line).into_iter();
+            while let Some(token) = tokens.next() {
+                match token.as_str() {
+                    "machine" => {
+                        netrc.push(current.take());
+                        let host = tokens.next()
+                            .ok_or_else(|| anyhow!("missing host after 'machine'"))?;
+                        current = Some((Some(host), Credentials::default()));
+                    }
+                    "default" => {
+                        netrc.push(current.take());
+                        current = Some((None, Credentials::default()));
+                    }
+                    "login" | "password" | "account" => {
+                        let value = tokens.next()
+                            .ok_or_else(|| anyhow!("missing value after '{token}'"))?;
+                        let (_, creds) = current.as_mut()
+                            .ok_or_else(|| anyhow!("'{token}' outside of a machine entry"))?;
+                        match token.as_str() {
+                            "login" => creds.login = value,
+                            "password" => creds.password = Some(value),
+                            _ => {}
+                        }
+                    }
+                    "macdef" => {
+                        // macro bodies run until the next blank line
+                        for line in lines.by_ref() {
+                            if line.trim().is_empty() {
+                                break;
+                            }
+                        }
+                        break;
+                    }
+                    t if t.starts_with('#') => break,
+                    t => return Err(anyhow!("unexpected token: {t}")),
+                }
+            }
+        }
+        netrc.push(current);
+        Ok(netrc)
+    }
+
+    /// credentials for the given host, if any.
+    pub fn find(&self, host: &str) -> Option<&Credentials> {
+        self.machines
+            .iter()
+            .find(|(machine, _)| machine.eq_ignore_ascii_case(host))
+            .map(|(_, creds)| creds)
+            .or(self.default.as_ref())
+    }
+
+    fn push(&mut self, entry: Option<(Option<String>, Credentials)>) {
+        match entry {
+            Some((Some(host), creds)) => self.machines.push((host, creds)),
+            // first default wins
+            Some((None, creds)) if self.default.is_none() => self.default = Some(creds),
+            _ => {}
+        }
+    }
+}
+
+/// ~/.netrc, if a home directory can be found.
+pub fn default_path() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|home| PathBuf::from(home).join(".netrc"))
+}
+
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut token = String::new();
+            while let Some(c) = chars.next() {
+                match c {
+                    '"' => break,
+                    '\\' => token.extend(chars.next()),
+                    c => token.push(c),
+                }
+            }
+            tokens.push(token);
+        } else {
+            let mut token = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                token.push(c);
+                chars.next();
+            }
+            tokens.push(token);
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_success() {
+        let data = r#"
+machine example.com login alice password s3cret
+# a comment
+machine api.example.com
+    login bob
+    password "with space"
+default login anon password guest
+"#;
+        let netrc = Netrc::parse(data).unwrap();
+        assert_eq!(netrc.find("example.com").unwrap().login, "alice");
+        assert_eq!(netrc.find("EXAMPLE.com").unwrap().password.as_deref(), Some("s3cret"), "host is case insensitive");
+        assert_eq!(netrc.find("api.example.com").unwrap().password.as_deref(), Some("with space"), "quoted values");
+        assert_eq!(netrc.find("other.com").unwrap().login, "anon", "falls back to default");
+    }
+
+    #[test]
+    fn parse_skips_macdef() {
+        let data = "machine a.com login a password b\nmacdef init\ncd /pub\nls\n\nmachine b.com login c password d\n";
+        let netrc = Netrc::parse(data).unwrap();
+        assert_eq!(netrc.find("b.com").unwrap().login, "c");
+        assert!(Netrc::parse("machine a.com login a\n").unwrap().find("c.com").is_none(), "no default");
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(Netrc::parse("machine").is_err(), "missing host");
+        assert!(Netrc::parse("login foo").is_err(), "login outside machine");
+        assert!(Netrc::parse("machine a.com bogus").is_err(), "unknown token");
+    }
+}
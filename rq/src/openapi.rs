@@ -0,0 +1,515 @@
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Method;
+use serde_json::{Map, Value};
+
+use crate::{Body, RequestSpec, ResponseSummary};
+
+const METHODS: [&str; 8] = ["get", "put", "post", "delete", "options", "head", "patch", "trace"];
+
+/// an OpenAPI 3 document, loaded from yaml or json.
+pub struct OpenApi {
+    doc: Value,
+}
+
+/// a single operation, found by its operationId.
+pub struct Operation<'a> {
+    api: &'a OpenApi,
+    method: Method,
+    path: String,
+    op: &'a Value,
+    path_item: &'a Value,
+}
+
+/// a place where the response didn't match the spec.
+#[derive(Debug, PartialEq)]
+pub struct Mismatch {
+    /// json pointer into the body, empty for the status
+    pub path: String,
+    pub message: String,
+}
+
+impl OpenApi {
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        Self::parse(&data).with_context(|| format!("bad openapi spec {}", path.display()))
+    }
+
+    /// yaml is a superset of json, so this takes either.
+    pub fn parse(data: &str) -> Result<Self> {
+        let doc: Value = serde_yaml::from_str(data)?;
+        if !doc.get("paths").is_some_and(Value::is_object) {
+            return Err(anyhow!("no paths found"));
+        }
+        Ok(OpenApi { doc })
+    }
+
+    pub fn operation(&self, operation_id: &str) -> Result<Operation<'_>> {
+        let paths = self.doc["paths"].as_object().into_iter().flatten();
+        for (path, path_item) in paths {
+            let path_item = self.resolve(path_item);
+            for method in METHODS {
+                let Some(op) = path_item.get(method) else {
+                    continue;
+                };
+                if op.get("operationId").and_then(Value::as_str) == Some(operation_id) {
+                    return Ok(Operation {
+                        api: self,
+                        method: method.to_uppercase().parse()?,
+                        path: path.clone(),
+                        op,
+                        path_item,
+                    });
+                }
+            }
+        }
+        Err(anyhow!("operation {operation_id} not found"))
+    }
+
+    /// the first server url, if there is one.
+    fn server(&self) -> Option<&str> {
+        self.doc.get("servers")?.get(0)?.get("url")?.as_str()
+    }
+
+    /// follows local $refs, e.g. #/components/schemas/User
+    fn resolve<'a>(&'a self, mut value: &'a Value) -> &'a Value {
+        // bounded, in case of a $ref cycle
+        for _ in 0..32 {
+            let Some(pointer) = value.get("$ref").and_then(Value::as_str) else {
+                break;
+            };
+            match pointer.strip_prefix('#').and_then(|p| self.doc.pointer(p)) {
+                Some(target) => value = target,
+                None => break,
+            }
+        }
+        value
+    }
+}
+
+impl Operation<'_> {
+    /// fills in the method, url, params and body. params are
+    /// name=value, name:=json for raw json values or
+    /// name==value to force a query param. they go where the
+    /// operation declares them, and in a json body otherwise.
+    pub fn apply(&self, spec: &mut RequestSpec, params: &[String]) -> Result<()> {
+        spec.method = self.method.clone();
+        spec.url = self.path.clone();
+        if spec.base.is_none() {
+            spec.base = Some(self.api.server()
+                .ok_or_else(|| anyhow!("the spec has no servers, use --base"))?
+                .to_string());
+        }
+
+        let declared = self.parameters();
+        let mut body = Map::new();
+        let params = params.iter().map(|p| parse_param(p)).collect::<Result<Vec<_>>>()?;
+        for (name, op, value) in &params {
+            let location = match op {
+                Op::Query => Some("query"),
+                _ => declared.iter()
+                    .find(|p| p.get("name").and_then(Value::as_str) == Some(name))
+                    .and_then(|p| p.get("in")?.as_str()),
+            };
+            let text = || match value {
+                Value::String(s) => s.clone(),
+                value => value.to_string(),
+            };
+            match location {
+                Some("path") => {
+                    spec.path_params.insert(name.to_string(), text());
+                }
+                Some("query") => spec.query.push((name.to_string(), text())),
+                Some("header") => spec.headers.push(format!("{name}: {}", text())),
+                Some("cookie") => {
+                    let cookie = match &spec.cookies {
+                        Some(cookies) => format!("{cookies}; {name}={}", text()),
+                        None => format!("{name}={}", text()),
+                    };
+                    spec.cookies = Some(cookie);
+                }
+                _ => {
+                    body.insert(name.to_string(), value.clone());
+                }
+            }
+        }
+
+        for param in &declared {
+            let required = param.get("required").and_then(Value::as_bool).unwrap_or(false);
+            let name = param.get("name").and_then(Value::as_str).unwrap_or_default();
+            let supplied = params.iter().any(|(n, _, _)| *n == name);
+            if required && !supplied {
+                return Err(anyhow!("missing required parameter {name}"));
+            }
+        }
+
+        if !body.is_empty() {
+            if self.op.get("requestBody").is_none() {
+                let names: Vec<_> = body.keys().cloned().collect();
+                return Err(anyhow!("unknown parameter(s) for {}: {}", self.path, names.join(", ")));
+            }
+            if spec.body.is_some() {
+                return Err(anyhow!("can't combine body parameters with --data"));
+            }
+            spec.body = Some(Body::Json(Value::Object(body)));
+        }
+        Ok(())
+    }
+
+    /// checks the status and json body against the
+    /// declared responses.
+    pub fn validate(&self, res: &ResponseSummary) -> Vec<Mismatch> {
+        let status = res.status().as_u16().to_string();
+        let responses = self.op.get("responses").and_then(Value::as_object);
+        let range = format!("{}XX", &status[..1]);
+        let Some(declared) = responses.and_then(|r| {
+            r.get(&status)
+                .or_else(|| r.get(&range))
+                .or_else(|| r.get(&range.to_lowercase()))
+                .or_else(|| r.get("default"))
+        }) else {
+            return vec![Mismatch { path: String::new(), message: format!("status {status} is not declared") }];
+        };
+
+        let declared = self.api.resolve(declared);
+        let schema = declared.get("content")
+            .and_then(Value::as_object)
+            .and_then(|content| {
+                content.iter()
+                    .find(|(media, _)| media.contains("json"))
+                    .and_then(|(_, media)| media.get("schema"))
+            });
+        let Some(schema) = schema else {
+            return Vec::new();
+        };
+        let body: Value = match res.json() {
+            Ok(body) => body,
+            Err(_) => return vec![Mismatch { path: String::new(), message: "body is not valid json".to_string() }],
+        };
+        let mut mismatches = Vec::new();
+        self.api.validate(schema, &body, "", &mut mismatches);
+        mismatches
+    }
+
+    /// path-level and operation-level parameters, with the
+    /// operation's taking precedence.
+    fn parameters(&self) -> Vec<&Value> {
+        let mut params: Vec<&Value> = Vec::new();
+        let op_params = self.op.get("parameters").and_then(Value::as_array).into_iter().flatten();
+        let path_params = self.path_item.get("parameters").and_then(Value::as_array).into_iter().flatten();
+        for param in op_params.chain(path_params) {
+            let param = self.api.resolve(param);
+            let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+            if !params.iter().any(|p| key(p) == key(param)) {
+                params.push(param);
+            }
+        }
+        params
+    }
+}
+
+impl OpenApi {
+    /// a json schema subset: types, nullable, enum/const,
+    /// properties, required, additionalProperties, items,
+    /// allOf/anyOf/oneOf and the numeric/length bounds.
+    fn validate(&self, schema: &Value, value: &Value, path: &str, mismatches: &mut Vec<Mismatch>) {
+        let schema = self.resolve(schema);
+        let mut fail = |message: String| mismatches.push(Mismatch { path: path.to_string(), message });
+
+        if value.is_null() && schema.get("nullable").and_then(Value::as_bool) == Some(true) {
+            return;
+        }
+        if let Some(types) = schema.get("type") {
+            let types: Vec<&str> = match types {
+                Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+                t => t.as_str().into_iter().collect(),
+            };
+            if !types.is_empty() && !types.iter().any(|t| type_matches(t, value)) {
+                fail(format!("expected {}, got {}", types.join(" or "), type_name(value)));
+                return;
+            }
+        }
+        if let Some(options) = schema.get("enum").and_then(Value::as_array) {
+            if !options.contains(value) {
+                fail(format!("{value} is not one of {}", Value::Array(options.clone())));
+            }
+        }
+        if let Some(expected) = schema.get("const") {
+            if expected != value {
+                fail(format!("expected {expected}, got {value}"));
+            }
+        }
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = schema.get("minimum").and_then(Value::as_f64) {
+                if n < min {
+                    fail(format!("{n} is less than the minimum {min}"));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(Value::as_f64) {
+                if n > max {
+                    fail(format!("{n} is more than the maximum {max}"));
+                }
+            }
+        }
+        if let Some(s) = value.as_str() {
+            let len = s.chars().count() as u64;
+            if schema.get("minLength").and_then(Value::as_u64).is_some_and(|min| len < min) {
+                fail(format!("string is shorter than minLength {}", schema["minLength"]));
+            }
+            if schema.get("maxLength").and_then(Value::as_u64).is_some_and(|max| len > max) {
+                fail(format!("string is longer than maxLength {}", schema["maxLength"]));
+            }
+        }
+
+        if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+            for sub in all {
+                self.validate(sub, value, path, mismatches);
+            }
+        }
+        for (keyword, exactly_one) in [("anyOf", false), ("oneOf", true)] {
+            if let Some(subs) = schema.get(keyword).and_then(Value::as_array) {
+                let passing = subs.iter()
+                    .filter(|sub| {
+                        let mut sub_mismatches = Vec::new();
+                        self.validate(sub, value, path, &mut sub_mismatches);
+                        sub_mismatches.is_empty()
+                    })
+                    .count();
+                if passing == 0 || (exactly_one && passing > 1) {
+                    mismatches.push(Mismatch {
+                        path: path.to_string(),
+                        message: format!("matches {passing} of the {keyword} schemas"),
+                    });
+                }
+            }
+        }
+
+        match value {
+            Value::Object(object) => self.validate_object(schema, object, path, mismatches),
+            Value::Array(items) => {
+                let len = items.len() as u64;
+                if schema.get("minItems").and_then(Value::as_u64).is_some_and(|min| len < min) {
+                    mismatches.push(Mismatch { path: path.to_string(), message: format!("fewer than {} items", schema["minItems"]) });
+                }
+                if schema.get("maxItems").and_then(Value::as_u64).is_some_and(|max| len > max) {
+                    mismatches.push(Mismatch { path: path.to_string(), message: format!("more than {} items", schema["maxItems"]) });
+                }
+                if let Some(item_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        self.validate(item_schema, item, &format!("{path}/{i}"), mismatches);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn validate_object(&self, schema: &Value, object: &Map<String, Value>, path: &str, mismatches: &mut Vec<Mismatch>) {
+        for name in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !object.contains_key(name) {
+                mismatches.push(Mismatch { path: path.to_string(), message: format!("missing required property {name}") });
+            }
+        }
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, child) in object {
+            let child_path = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+            match (properties.and_then(|p| p.get(key)), schema.get("additionalProperties")) {
+                (Some(property), _) => self.validate(property, child, &child_path, mismatches),
+                (None, Some(Value::Bool(false))) => mismatches.push(Mismatch {
+                    path: child_path,
+                    message: "property is not allowed".to_string(),
+                }),
+                (None, Some(additional)) if additional.is_object() => {
+                    self.validate(additional, child, &child_path, mismatches)
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn type_matches(t: &str, value: &Value) -> bool {
+    match t {
+        "integer" => value.is_i64() || value.is_u64() || value.as_f64().is_some_and(|n| n.fract() == 0.0),
+        "number" => value.is_number(),
+        t => type_name(value) == t,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)
+        } else {
+            write!(f, "at {}: {}", self.path, self.message)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Op {
+    /// name=value
+    Text,
+    /// name:=json
+    Json,
+    /// name==value
+    Query,
+}
+
+/// splits a param on its first =, which with
+/// the character on either side gives the operator.
+fn parse_param(param: &str) -> Result<(&str, Op, Value)> {
+    let i = param.find('=')
+        .ok_or_else(|| anyhow!("expected name=value, name:=json or name==value, got {:?}", param))?;
+    let (name, rest) = param.split_at(i);
+    if let Some(name) = name.strip_suffix(':') {
+        let json = &rest[1..];
+        let value = serde_json::from_str(json)
+            .with_context(|| format!("bad json for {name}: {json}"))?;
+        return Ok((name, Op::Json, value));
+    }
+    match rest.strip_prefix("==") {
+        Some(value) => Ok((name, Op::Query, Value::String(value.to_string()))),
+        None => Ok((name, Op::Text, Value::String(rest[1..].to_string()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const SPEC: &str = r##"
+openapi: 3.0.3
+servers:
+  - url: https://api.example.com/v1
+paths:
+  /users/{id}:
+    parameters:
+      - name: id
+        in: path
+        required: true
+    get:
+      operationId: getUser
+      parameters:
+        - name: verbose
+          in: query
+        - $ref: '#/components/parameters/Trace'
+      responses:
+        '200':
+          content:
+            application/json:
+              schema:
+                $ref: '#/components/schemas/User'
+        4XX:
+          description: oops
+  /users:
+    post:
+      operationId: createUser
+      requestBody:
+        content:
+          application/json: {}
+      responses:
+        '201':
+          description: created
+components:
+  parameters:
+    Trace:
+      name: X-Trace
+      in: header
+  schemas:
+    User:
+      type: object
+      required: [id, name]
+      additionalProperties: false
+      properties:
+        id:
+          type: integer
+          minimum: 1
+        name:
+          type: string
+        role:
+          enum: [admin, user]
+        email:
+          type: string
+          nullable: true
+        tags:
+          type: array
+          items:
+            type: string
+"##;
+
+    fn validate(schema_name: &str, value: Value) -> Vec<String> {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let schema = json!({"$ref": format!("#/components/schemas/{schema_name}")});
+        let mut mismatches = Vec::new();
+        api.validate(&schema, &value, "", &mut mismatches);
+        mismatches.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn apply_builds_spec() {
+        let api = OpenApi::parse(SPEC).unwrap();
+        let op = api.operation("getUser").unwrap();
+        let mut spec = RequestSpec::default();
+        op.apply(&mut spec, &["id=42".to_string(), "verbose=true".to_string(), "X-Trace=abc".to_string()]).unwrap();
+        assert_eq!(spec.method, Method::GET);
+        assert_eq!(spec.resolve_url().unwrap().as_str(), "https://api.example.com/v1/users/42?verbose=true");
+        assert_eq!(spec.headers, ["X-Trace: abc"]);
+
+        let mut spec = RequestSpec::default();
+        assert!(op.apply(&mut spec, &[]).is_err(), "id is required");
+
+        let op = api.operation("createUser").unwrap();
+        let mut spec = RequestSpec::default().base("http://localhost:8080");
+        op.apply(&mut spec, &["name=x".to_string(), "age:=3".to_string()]).unwrap();
+        assert_eq!(spec.method, Method::POST);
+        assert_eq!(spec.base.as_deref(), Some("http://localhost:8080"), "--base wins over servers");
+        assert_eq!(spec.body, Some(Body::Json(json!({"name": "x", "age": 3}))));
+
+        let op = api.operation("getUser").unwrap();
+        let mut spec = RequestSpec::default();
+        op.apply(&mut spec, &["id:=42".to_string(), "verbose:=true".to_string(), "page==2".to_string()]).unwrap();
+        assert_eq!(spec.resolve_url().unwrap().as_str(), "https://api.example.com/v1/users/42?verbose=true&page=2", "raw values go where they're declared");
+        assert_eq!(spec.body, None);
+
+        assert!(api.operation("nope").is_err());
+    }
+
+    #[test]
+    fn parses_params() {
+        assert_eq!(parse_param("a=b=c").unwrap(), ("a", Op::Text, json!("b=c")));
+        assert_eq!(parse_param("a:=[1]").unwrap(), ("a", Op::Json, json!([1])));
+        assert_eq!(parse_param("a==b").unwrap(), ("a", Op::Query, json!("b")));
+        assert_eq!(parse_param("a=").unwrap(), ("a", Op::Text, json!("")));
+        assert!(parse_param("a:=nope").is_err());
+        assert!(parse_param("a").is_err());
+    }
+
+    #[test]
+    fn validate_schema() {
+        assert!(validate("User", json!({"id": 1, "name": "a", "email": null, "tags": ["x"]})).is_empty());
+        assert_eq!(validate("User", json!({"id": "1", "name": "a"})), ["at /id: expected integer, got string"]);
+        assert_eq!(validate("User", json!({"id": 0, "name": "a"})), ["at /id: 0 is less than the minimum 1"]);
+        assert_eq!(validate("User", json!({"name": "a"})), ["missing required property id"]);
+        assert_eq!(validate("User", json!({"id": 1, "name": "a", "extra": 1})), ["at /extra: property is not allowed"]);
+        assert_eq!(validate("User", json!({"id": 1, "name": "a", "role": "root"})), [r#"at /role: "root" is not one of ["admin","user"]"#]);
+        assert_eq!(validate("User", json!({"id": 1, "name": "a", "tags": [1]})), ["at /tags/0: expected string, got number"]);
+        assert_eq!(validate("User", json!([])), ["expected object, got array"]);
+    }
+}
@@ -0,0 +1,303 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::time::Duration;
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use reqwest::{StatusCode, Version};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use encoding_rs::Encoding;
+
+/// how much of the body to look at
+/// when guessing if it's binary.
+const SNIFF_LEN: usize = 8192;
+
+/// a completed response, either fresh off
+/// the wire or from the cache.
+#[derive(Debug, Clone, Default)]
+pub struct ResponseSummary {
+    pub(crate) version: Option<Version>,
+    pub(crate) status: StatusCode,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+    pub(crate) elapsed: Duration,
+    /// time until the headers arrived.
+    pub(crate) headers_elapsed: Duration,
+    pub(crate) redirects: Vec<Redirect>,
+    pub(crate) charset: Option<&'static Encoding>,
+    /// served from the cache, whether or not
+    /// the server was asked (a 304)
+    pub(crate) cached: bool,
+}
+
+/// a redirect that was followed on
+/// the way to the final response.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Redirect {
+    pub url: String,
+    pub status: u16,
+    pub location: String,
+}
+
+impl ResponseSummary {
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// None when the cache answered without
+    /// asking the server.
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+
+    pub fn headers_elapsed(&self) -> Duration {
+        self.headers_elapsed
+    }
+
+    /// redirects followed before this
+    /// response, oldest first.
+    pub fn redirects(&self) -> &[Redirect] {
+        &self.redirects
+    }
+
+    pub fn is_cached(&self) -> bool {
+        self.cached
+    }
+
+    /// decode the body with this charset (e.g. iso-8859-1,
+    /// shift_jis) instead of the one from Content-Type.
+    pub fn set_charset(&mut self, label: &str) -> Result<()> {
+        let encoding = Encoding::for_label(label.trim().as_bytes())
+            .ok_or_else(|| anyhow!("unknown charset: {label}"))?;
+        self.charset = Some(encoding);
+        Ok(())
+    }
+
+    /// decodes the body using the charset from
+    /// Content-Type, defaulting to utf-8.
+    pub fn text(&self) -> String {
+        let encoding = self.charset
+            .or_else(|| {
+                let charset = self.mime()?.get_param("charset")?.as_str().to_string();
+                Encoding::for_label(charset.as_bytes())
+            })
+            .unwrap_or(encoding_rs::UTF_8);
+        let (text, _, _) = encoding.decode(&self.body);
+        text.into_owned()
+    }
+
+    /// guesses whether the body is binary, first from
+    /// Content-Type and then by looking for NUL bytes
+    /// or invalid utf-8.
+    pub fn is_binary(&self) -> bool {
+        if let Some(mime) = self.mime() {
+            if mime.type_() == mime::TEXT
+                || mime.get_param("charset").is_some()
+                || matches!(mime.subtype(), mime::JSON | mime::XML | mime::JAVASCRIPT | mime::WWW_FORM_URLENCODED)
+                || matches!(mime.suffix(), Some(mime::JSON | mime::XML)) {
+                return false;
+            }
+            if matches!(mime.type_(), mime::IMAGE | mime::AUDIO | mime::VIDEO | mime::FONT)
+                || mime.subtype() == mime::OCTET_STREAM
+                || mime.subtype() == mime::PDF {
+                return true;
+            }
+        }
+        let sample = &self.body[..self.body.len().min(SNIFF_LEN)];
+        if sample.contains(&0) {
+            return true;
+        }
+        match std::str::from_utf8(sample) {
+            Ok(_) => false,
+            // a multi-byte char may have been cut off by the sample
+            Err(e) => e.error_len().is_some(),
+        }
+    }
+
+    /// writes the body as-is.
+    pub fn write_body(&self, w: &mut impl Write) -> Result<()> {
+        w.write_all(&self.body)?;
+        w.flush()?;
+        Ok(())
+    }
+
+    fn mime(&self) -> Option<mime::Mime> {
+        self.headers.get(CONTENT_TYPE)?.to_str().ok()?.parse().ok()
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_slice(&self.body).with_context(|| "response body is not valid json")
+    }
+
+    /// prints the status line and headers.
+    pub fn print_head(&self) {
+        match self.version {
+            Some(version) if self.cached => println!("(revalidated) {:?} {:?} {:?}", version, self.status, self.status.canonical_reason().unwrap_or_default()),
+            Some(version) => println!("{:?} {:?} {:?}", version, self.status, self.status.canonical_reason().unwrap_or_default()),
+            None => println!("(cached) {:?} {:?}", self.status, self.status.canonical_reason().unwrap_or_default()),
+        }
+        for (h, v) in &self.headers {
+            println!("{:?}: {:?}", h, v)
+        }
+        println!();
+    }
+
+    /// prints the body as text, optionally
+    /// pretty-printing it as json.
+    pub fn print_body(&self, pretty: bool) -> Result<()> {
+        if pretty {
+            let json_res: Value = self.json()?;
+            match serde_json::to_string_pretty(&json_res) {
+                Ok(pp) => println!("{pp}"),
+                // just print it
+                Err(_) => println!("{}", self.text()),
+            }
+        } else {
+            println!("{}", self.text());
+        }
+        Ok(())
+    }
+
+    pub fn expect_status(&self, status: u16) -> Result<&Self> {
+        if self.status.as_u16() != status {
+            return Err(anyhow!("expected status {status}, got {}", self.status));
+        }
+        Ok(self)
+    }
+
+    pub fn expect_success(&self) -> Result<&Self> {
+        if !self.status.is_success() {
+            return Err(anyhow!("expected a success status, got {}", self.status));
+        }
+        Ok(self)
+    }
+
+    pub fn expect_header(&self, name: &str, value: &str) -> Result<&Self> {
+        let found = self.headers.get_all(name).iter().any(|v| v == value);
+        if !found {
+            return Err(anyhow!("expected header {name}: {value}, got {:?}", self.headers.get(name)));
+        }
+        Ok(self)
+    }
+
+    pub fn expect_body_contains(&self, needle: &str) -> Result<&Self> {
+        if !self.text().contains(needle) {
+            return Err(anyhow!("expected body to contain {needle:?}"));
+        }
+        Ok(self)
+    }
+
+    /// checks the value at a json pointer,
+    /// e.g. /users/0/name
+    pub fn expect_json(&self, pointer: &str, expected: &Value) -> Result<&Self> {
+        let body: Value = self.json()?;
+        match body.pointer(pointer) {
+            Some(actual) if actual == expected => Ok(self),
+            Some(actual) => Err(anyhow!("expected {expected} at {pointer}, got {actual}")),
+            None => Err(anyhow!("nothing found at {pointer}")),
+        }
+    }
+}
+
+/// headers by name, with repeated headers
+/// (set-cookie, say) as an array of their values.
+pub(crate) fn header_map(headers: &HeaderMap) -> BTreeMap<String, Value> {
+    let mut map = BTreeMap::new();
+    for name in headers.keys() {
+        let mut values = headers.get_all(name)
+            .iter()
+            .map(|v| Value::String(String::from_utf8_lossy(v.as_bytes()).into_owned()))
+            .collect::<Vec<_>>();
+        let value = match values.len() {
+            1 => values.remove(0),
+            _ => Value::Array(values),
+        };
+        map.insert(name.to_string(), value);
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+    use serde_json::json;
+
+    fn summary(content_type: &'static str, body: &[u8]) -> ResponseSummary {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        ResponseSummary {
+            version: Some(Version::HTTP_11),
+            status: StatusCode::OK,
+            headers,
+            body: body.to_vec(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn text_uses_charset() {
+        let res = summary("text/plain; charset=iso-8859-1", b"caf\xe9");
+        assert_eq!(res.text(), "café");
+        assert_eq!(summary("text/plain", "café".as_bytes()).text(), "café", "defaults to utf-8");
+    }
+
+    #[test]
+    fn charset_override() {
+        let mut res = summary("text/plain", b"\x82\xa0");
+        res.set_charset("shift_jis").unwrap();
+        assert_eq!(res.text(), "あ");
+        assert!(res.set_charset("bogus").is_err());
+    }
+
+    #[test]
+    fn binary_detection() {
+        assert!(summary("image/png", b"\x89PNG").is_binary());
+        assert!(summary("application/octet-stream", b"hello").is_binary(), "trusts content-type");
+        assert!(!summary("application/json", b"{}").is_binary());
+        assert!(!summary("application/vnd.api+json", b"{}").is_binary(), "+json suffix");
+        assert!(!summary("text/plain; charset=iso-8859-1", b"caf\xe9").is_binary());
+        assert!(summary("", b"abc\x00def").is_binary(), "sniffs NUL bytes");
+        assert!(summary("", b"\xff\xfe\xfd").is_binary(), "sniffs invalid utf-8");
+        assert!(!summary("", "plain café".as_bytes()).is_binary());
+    }
+
+    #[test]
+    fn repeated_headers_are_an_array() {
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/plain"));
+        headers.append("set-cookie", HeaderValue::from_static("a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT"));
+        headers.append("set-cookie", HeaderValue::from_static("b=2"));
+        let map = header_map(&headers);
+        assert_eq!(map["content-type"], json!("text/plain"));
+        assert_eq!(map["set-cookie"], json!(["a=1; Expires=Wed, 21 Oct 2026 07:28:00 GMT", "b=2"]));
+    }
+
+    #[test]
+    fn expectations() {
+        let res = summary("application/json", br#"{"users": [{"name": "a"}]}"#);
+        assert!(res.expect_status(200).is_ok());
+        assert!(res.expect_status(404).is_err());
+        assert!(res.expect_success().is_ok());
+        assert!(res.expect_header("content-type", "application/json").is_ok());
+        assert!(res.expect_header("content-type", "text/html").is_err());
+        assert!(res.expect_body_contains("users").is_ok());
+        assert!(res.expect_json("/users/0/name", &json!("a")).is_ok());
+        assert!(res.expect_json("/users/0/name", &json!("b")).is_err());
+        assert!(res.expect_json("/nope", &json!(null)).is_err());
+    }
+}
@@ -0,0 +1,904 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, COOKIE, USER_AGENT};
+use reqwest::{
+    Method,
+    StatusCode,
+    NoProxy,
+    Proxy,
+    blocking::{Request, Client, multipart},
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use url::Url;
+
+use crate::cache::{Cache, CacheKey};
+use crate::netrc::{self, Netrc};
+use crate::response::{Redirect, ResponseSummary};
+use crate::upload;
+
+const USER_AGENT_DEFAULT: &str = "github.com/davemolk/rusty-bits/rq";
+
+/// same limit reqwest uses by default.
+const MAX_REDIRECTS: usize = 10;
+
+/// characters that can't appear as-is in a path segment.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ').add(b'"').add(b'#').add(b'<').add(b'>').add(b'`')
+    .add(b'?').add(b'{').add(b'}').add(b'/').add(b'%');
+
+/// everything needed to make a request. build one up with
+/// the chainable setters, or load it from json/toml:
+///
+/// ```no_run
+/// use rq::RequestSpec;
+///
+/// let res = RequestSpec::new("https://httpbin.org/get")
+///     .header("Accept: application/json")
+///     .bearer("token")
+///     .execute()?;
+/// res.expect_status(200)?;
+/// # Ok::<(), anyhow::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RequestSpec {
+    /// absolute, or relative to base. may contain
+    /// :name or {name} path param placeholders.
+    pub url: String,
+    pub base: Option<String>,
+    /// appended to the url, percent-encoded.
+    pub query: Vec<(String, String)>,
+    pub path_params: BTreeMap<String, String>,
+    #[serde(with = "method_serde")]
+    pub method: Method,
+    /// Name:value or Name=value, as with -H.
+    /// an empty value unsets the header (accept is
+    /// sent empty instead, as reqwest would add */*)
+    /// and @path loads headers from a json file.
+    pub headers: Vec<String>,
+    /// raw Cookie header, or @path to read it from a file.
+    pub cookies: Option<String>,
+    pub auth: Option<Auth>,
+    pub body: Option<Body>,
+    /// send file bodies with chunked transfer
+    /// encoding instead of Content-Length.
+    pub chunked: bool,
+    /// show upload progress on stderr for
+    /// file and stdin bodies.
+    pub progress: bool,
+    /// fractions of a second allowed, e.g. 0.5.
+    pub timeout_seconds: Option<f64>,
+    pub follow_redirects: bool,
+    pub http2: bool,
+    pub user_agent: Option<String>,
+    pub proxy: Option<String>,
+    /// formatted as user:pw
+    pub proxy_user: Option<String>,
+    /// comma-separated hosts, "*" for no proxy at all.
+    pub noproxy: Option<String>,
+    pub netrc: bool,
+    pub netrc_file: Option<PathBuf>,
+    pub cache: bool,
+    pub cache_dir: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Auth {
+    Basic { user: String, password: Option<String> },
+    Bearer { token: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Body {
+    Text(String),
+    /// streamed from a file, with Content-Type
+    /// guessed from the extension
+    File(PathBuf),
+    /// streamed from stdin, chunked
+    Stdin,
+    /// sent with Content-Type: application/json
+    Json(Value),
+    /// multipart/form-data. values that are paths to
+    /// existing files are sent as files, otherwise as text.
+    Form(BTreeMap<String, String>),
+}
+
+/// a request that's ready to go, so
+/// it can be inspected before sending.
+pub struct PreparedRequest<'a> {
+    spec: &'a RequestSpec,
+    client: Client,
+    request: Request,
+    redirects: Arc<Mutex<Vec<Redirect>>>,
+}
+
+/// headers parsed from -H, along with
+/// any that should not be sent at all.
+#[derive(Debug, Default)]
+struct Headers {
+    set: HeaderMap,
+    unset: Vec<HeaderName>,
+}
+
+impl Default for RequestSpec {
+    fn default() -> Self {
+        RequestSpec {
+            url: String::new(),
+            base: None,
+            query: Vec::new(),
+            path_params: BTreeMap::new(),
+            method: Method::GET,
+            headers: Vec::new(),
+            cookies: None,
+            auth: None,
+            body: None,
+            chunked: false,
+            progress: false,
+            timeout_seconds: None,
+            follow_redirects: true,
+            http2: false,
+            user_agent: None,
+            proxy: None,
+            proxy_user: None,
+            noproxy: None,
+            netrc: false,
+            netrc_file: None,
+            cache: false,
+            cache_dir: None,
+        }
+    }
+}
+
+impl RequestSpec {
+    pub fn new(url: impl Into<String>) -> Self {
+        RequestSpec { url: url.into(), ..Default::default() }
+    }
+
+    pub fn from_json(data: &str) -> Result<Self> {
+        serde_json::from_str(data).with_context(|| "bad request spec")
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_toml(data: &str) -> Result<Self> {
+        toml::from_str(data).with_context(|| "bad request spec")
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// loads a spec from a .json or .toml file.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = fs::read_to_string(path)
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => Self::from_toml(&data),
+            _ => Self::from_json(&data),
+        }
+    }
+
+    pub fn base(mut self, base: impl Into<String>) -> Self {
+        self.base = Some(base.into());
+        self
+    }
+
+    pub fn query(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.query.push((key.into(), value.into()));
+        self
+    }
+
+    pub fn path_param(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.path_params.insert(name.into(), value.into());
+        self
+    }
+
+    pub fn method(mut self, method: Method) -> Self {
+        self.method = method;
+        self
+    }
+
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.headers.push(header.into());
+        self
+    }
+
+    pub fn cookies(mut self, cookies: impl Into<String>) -> Self {
+        self.cookies = Some(cookies.into());
+        self
+    }
+
+    pub fn basic(mut self, user: impl Into<String>, password: Option<String>) -> Self {
+        self.auth = Some(Auth::Basic { user: user.into(), password });
+        self
+    }
+
+    pub fn bearer(mut self, token: impl Into<String>) -> Self {
+        self.auth = Some(Auth::Bearer { token: token.into() });
+        self
+    }
+
+    pub fn body(mut self, body: Body) -> Self {
+        self.body = Some(body);
+        self
+    }
+
+    pub fn json(self, value: Value) -> Self {
+        self.body(Body::Json(value))
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout_seconds = Some(timeout.as_secs_f64());
+        self
+    }
+
+    pub fn follow_redirects(mut self, follow: bool) -> Self {
+        self.follow_redirects = follow;
+        self
+    }
+
+    pub fn user_agent(mut self, user_agent: impl Into<String>) -> Self {
+        self.user_agent = Some(user_agent.into());
+        self
+    }
+
+    pub fn proxy(mut self, proxy: impl Into<String>) -> Self {
+        self.proxy = Some(proxy.into());
+        self
+    }
+
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// builds the client and request without sending anything.
+    pub fn prepare(&self) -> Result<PreparedRequest<'_>> {
+        let headers = add_headers(&self.headers).with_context(|| "adding headers")?;
+        let redirects = Arc::default();
+        let client = build_client(self, &redirects)?;
+        let request = build_request(self, &client, headers)?;
+        Ok(PreparedRequest { spec: self, client, request, redirects })
+    }
+
+    pub fn execute(&self) -> Result<ResponseSummary> {
+        self.prepare()?.send()
+    }
+
+    /// the final url: path params substituted, joined
+    /// onto base if relative, with the query appended.
+    pub fn resolve_url(&self) -> Result<Url> {
+        let path = substitute_path_params(&self.url, &self.path_params)?;
+        let mut url = match (Url::parse(&path), &self.base) {
+            (Ok(url), _) => url,
+            (Err(url::ParseError::RelativeUrlWithoutBase), Some(base)) => {
+                // plain concatenation, so a base with a path
+                // (https://host/v1) keeps it
+                let joined = format!("{}/{}", base.trim_end_matches('/'), path.trim_start_matches('/'));
+                Url::parse(&joined)
+                    .with_context(|| format!("{} cannot be parsed as url", joined))?
+            }
+            (Err(e), _) => return Err(anyhow!(e))
+                .with_context(|| format!("{} cannot be parsed as url", path)),
+        };
+        if !self.query.is_empty() {
+            let mut pairs = url.query_pairs_mut();
+            for (key, value) in &self.query {
+                pairs.append_pair(key, value);
+            }
+        }
+        Ok(url)
+    }
+
+    fn uses_cache(&self) -> bool {
+        self.cache || self.cache_dir.is_some()
+    }
+}
+
+impl PreparedRequest<'_> {
+    pub fn request(&self) -> &Request {
+        &self.request
+    }
+
+    /// prints the request line, headers and timeout.
+    pub fn print(&self) {
+        let req = &self.request;
+        println!("{:?}", req.version());
+        println!("{:?}", req.url().as_str());
+        println!("{:?}", req.method());
+        for (h, v) in req.headers() {
+            println!("{:?}: {:?}", h, v);
+        };
+        if let Some(t) = req.timeout() {
+            println!("timeout: {:?}", t)
+        }
+        println!();
+    }
+
+    /// executes the request, going through
+    /// the cache if one is enabled.
+    pub fn send(self) -> Result<ResponseSummary> {
+        let spec = self.spec;
+        let mut res = if spec.uses_cache() {
+            let dir = match &spec.cache_dir {
+                Some(dir) => dir.clone(),
+                None => Cache::default_dir().ok_or_else(|| anyhow!("no cache dir found, use --cache-dir"))?,
+            };
+            execute_cached(&Cache::new(dir)?, &self.client, self.request)?
+        } else {
+            execute(&self.client, self.request)?
+        };
+        res.redirects = std::mem::take(&mut self.redirects.lock().unwrap());
+        Ok(res)
+    }
+
+    /// like send, but streams the body into w rather than
+    /// holding it in memory. the returned summary has an
+    /// empty body.
+    pub fn send_to(self, w: &mut impl Write) -> Result<ResponseSummary> {
+        if self.spec.uses_cache() {
+            // the cache needs the whole body anyway
+            let mut res = self.send()?;
+            res.write_body(w)?;
+            res.body.clear();
+            return Ok(res);
+        }
+        let start = Instant::now();
+        let mut res = self.client.execute(self.request)?;
+        let headers_elapsed = start.elapsed();
+        let version = res.version();
+        let status = res.status();
+        let headers = res.headers().clone();
+        io::copy(&mut res, w).with_context(|| "writing response body")?;
+        w.flush()?;
+        Ok(ResponseSummary {
+            version: Some(version),
+            status,
+            headers,
+            elapsed: start.elapsed(),
+            headers_elapsed,
+            redirects: std::mem::take(&mut self.redirects.lock().unwrap()),
+            ..Default::default()
+        })
+    }
+}
+
+impl Headers {
+    fn add(&mut self, name: HeaderName, value: Option<HeaderValue>) {
+        match value {
+            Some(value) => {
+                self.unset.retain(|n| n != name);
+                self.set.append(name, value);
+            }
+            None => {
+                self.set.remove(&name);
+                self.unset.push(name);
+            }
+        }
+    }
+
+    fn is_unset(&self, name: &HeaderName) -> bool {
+        self.unset.contains(name)
+    }
+}
+
+fn execute(client: &Client, req: Request) -> Result<ResponseSummary> {
+    let start = Instant::now();
+    let res = client.execute(req)?;
+    let headers_elapsed = start.elapsed();
+    let version = res.version();
+    let status = res.status();
+    let headers = res.headers().clone();
+    let body = res.bytes()?.to_vec();
+    Ok(ResponseSummary { version: Some(version), status, headers, body, elapsed: start.elapsed(), headers_elapsed, ..Default::default() })
+}
+
+/// serves fresh entries straight from the cache, revalidates
+/// stale ones and stores whatever comes back.
+fn execute_cached(cache: &Cache, client: &Client, mut req: Request) -> Result<ResponseSummary> {
+    let start = Instant::now();
+    let key = CacheKey::new(req.method(), req.url().as_str(), req.headers());
+    let entry = if key.is_cacheable() { cache.lookup(&key)? } else { None };
+
+    if let Some(entry) = &entry {
+        if entry.is_fresh() {
+            return Ok(ResponseSummary {
+                version: None,
+                status: entry.status(),
+                headers: entry.headers(),
+                body: entry.body.clone(),
+                elapsed: start.elapsed(),
+                cached: true,
+                ..Default::default()
+            });
+        }
+        entry.add_validators(req.headers_mut());
+    }
+
+    let res = execute(client, req)?;
+    match entry {
+        Some(entry) if res.status == StatusCode::NOT_MODIFIED => {
+            let entry = cache.refresh(&key, entry, &res.headers)?;
+            Ok(ResponseSummary {
+                version: res.version,
+                status: entry.status(),
+                headers: entry.headers(),
+                body: entry.body,
+                elapsed: start.elapsed(),
+                headers_elapsed: res.headers_elapsed,
+                cached: true,
+                ..Default::default()
+            })
+        }
+        _ => {
+            cache.store(&key, res.status, &res.headers, &res.body)?;
+            Ok(res)
+        }
+    }
+}
+
+fn build_client(spec: &RequestSpec, redirects: &Arc<Mutex<Vec<Redirect>>>) -> Result<Client> {
+    let mut client = reqwest::blocking::ClientBuilder::new();
+
+    if spec.follow_redirects {
+        // keep track of each hop so it can be reported
+        let redirects = Arc::clone(redirects);
+        client = client.redirect(reqwest::redirect::Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error("too many redirects");
+            }
+            if let Some(from) = attempt.previous().last() {
+                redirects.lock().unwrap().push(Redirect {
+                    url: from.to_string(),
+                    status: attempt.status().as_u16(),
+                    location: attempt.url().to_string(),
+                });
+            }
+            attempt.follow()
+        }));
+    } else {
+        client = client.redirect(reqwest::redirect::Policy::none());
+    }
+
+    if spec.http2 {
+        client = client.http2_prior_knowledge();
+    }
+
+    if spec.noproxy.as_deref().map(str::trim) == Some("*") {
+        client = client.no_proxy();
+    } else {
+        let proxies = match &spec.proxy {
+            Some(proxy) => vec![Proxy::all(proxy).with_context(|| format!("invalid proxy: {}", proxy))?],
+            None => env_proxies()?,
+        };
+        for proxy in proxies {
+            client = client.proxy(configure_proxy(proxy, spec)?);
+        }
+    }
+
+    let client = client.build().with_context(|| "building client")?;
+    Ok(client)
+}
+
+/// proxies set in the environment. these are built
+/// here rather than left to reqwest so that --proxy-user
+/// and --noproxy apply to them too.
+fn env_proxies() -> Result<Vec<Proxy>> {
+    let mut proxies = Vec::new();
+    if let Some(p) = env_var(&["https_proxy", "HTTPS_PROXY"]) {
+        proxies.push(Proxy::https(&p).with_context(|| format!("invalid HTTPS_PROXY: {}", p))?);
+    }
+    if let Some(p) = env_var(&["http_proxy", "HTTP_PROXY"]) {
+        proxies.push(Proxy::http(&p).with_context(|| format!("invalid HTTP_PROXY: {}", p))?);
+    }
+    if let Some(p) = env_var(&["all_proxy", "ALL_PROXY"]) {
+        proxies.push(Proxy::all(&p).with_context(|| format!("invalid ALL_PROXY: {}", p))?);
+    }
+    Ok(proxies)
+}
+
+fn configure_proxy(mut proxy: Proxy, spec: &RequestSpec) -> Result<Proxy> {
+    if let Some(proxy_user) = &spec.proxy_user {
+        let (user, pw) = split_user_pw(proxy_user)
+            .ok_or_else(|| anyhow!("malformed proxy auth: {}", proxy_user))?;
+        proxy = proxy.basic_auth(user, pw);
+    }
+    let no_proxy = match &spec.noproxy {
+        Some(hosts) => NoProxy::from_string(hosts),
+        None => NoProxy::from_env(),
+    };
+    Ok(proxy.no_proxy(no_proxy))
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+    names.iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|v| !v.is_empty())
+}
+
+/// splits user:pw on the first colon so
+/// passwords may contain colons.
+pub(crate) fn split_user_pw(user_pw: &str) -> Option<(&str, &str)> {
+    user_pw.split_once(':')
+}
+
+fn build_request(spec: &RequestSpec, client: &Client, headers: Headers) -> Result<Request> {
+    let url = spec.resolve_url()?;
+
+    let mut req_builder = client.request(spec.method.clone(), url.clone());
+
+    let has_auth_header = headers.set.contains_key(AUTHORIZATION);
+    let has_content_type = headers.set.contains_key(CONTENT_TYPE) || headers.is_unset(&CONTENT_TYPE);
+    let mut set = headers.set;
+    // the defaults go on the request rather than the client, so
+    // the cache key and the exchange record see what's sent
+    if !set.contains_key(USER_AGENT) && !headers.unset.contains(&USER_AGENT) {
+        let ua = spec.user_agent.as_deref().unwrap_or(USER_AGENT_DEFAULT);
+        set.insert(USER_AGENT, HeaderValue::from_str(ua).with_context(|| format!("invalid user agent {:?}", ua))?);
+    }
+    if headers.unset.contains(&ACCEPT) {
+        // reqwest adds */* to a request without an accept,
+        // so an empty one is as close to unset as it gets
+        set.insert(ACCEPT, HeaderValue::from_static(""));
+    } else if !set.contains_key(ACCEPT) {
+        // what reqwest would add anyway
+        set.insert(ACCEPT, HeaderValue::from_static("*/*"));
+    }
+    req_builder = req_builder.headers(set);
+
+    if let Some(cookies) = &spec.cookies {
+        let cookie_header = add_cookies(cookies)?;
+        req_builder = req_builder.headers(cookie_header);
+    }
+
+    match &spec.auth {
+        Some(Auth::Basic { user, password }) => {
+            req_builder = req_builder.basic_auth(user, password.as_ref());
+        }
+        Some(Auth::Bearer { token }) => {
+            req_builder = req_builder.bearer_auth(token);
+        }
+        None => {}
+    }
+
+    if spec.netrc || spec.netrc_file.is_some() {
+        if let Some(creds) = netrc_credentials(spec, &url, has_auth_header)? {
+            req_builder = req_builder.basic_auth(creds.login, creds.password);
+        }
+    }
+
+    if let Some(t) = spec.timeout_seconds {
+        let timeout = Duration::try_from_secs_f64(t)
+            .with_context(|| format!("bad timeout {t}"))?;
+        req_builder = req_builder.timeout(timeout);
+    }
+
+    match &spec.body {
+        Some(Body::Text(text)) => {
+            req_builder = req_builder.body(text.clone());
+        }
+        Some(Body::File(path)) => {
+            let file = fs::File::open(path)
+                .with_context(|| format!("failed to open {}", path.display()))?;
+            let len = file.metadata()
+                .with_context(|| format!("failed to read {}", path.display()))?
+                .len();
+            if !has_content_type {
+                if let Some(content_type) = upload::content_type(path) {
+                    req_builder = req_builder.header(CONTENT_TYPE, content_type);
+                }
+            }
+            let len = (!spec.chunked).then_some(len);
+            req_builder = req_builder.body(upload::body(file, len, spec.progress));
+        }
+        Some(Body::Stdin) => {
+            req_builder = req_builder.body(upload::body(io::stdin(), None, spec.progress));
+        }
+        Some(Body::Json(value)) => {
+            req_builder = req_builder.json(value);
+        }
+        // todo: allow files for form data too
+        Some(Body::Form(fields)) => {
+            let mut form = multipart::Form::new();
+            for (key, form_value) in fields {
+                // check if it's a file first, otherwise use as text
+                let path = Path::new(form_value);
+                if path.exists() {
+                    form = form.file(key.to_owned(), path)?;
+                } else {
+                    form = form.text(key.to_owned(), form_value.to_owned());
+                }
+            }
+            req_builder = req_builder.multipart(form);
+        }
+        None => {}
+    }
+
+    let mut req = req_builder.build().with_context(|| "building request")?;
+    // whatever auth, netrc, cookies or the body added
+    for name in headers.unset.iter().filter(|&name| name != ACCEPT) {
+        req.headers_mut().remove(name);
+    }
+    Ok(req)
+}
+
+/// replaces /:name and {name} placeholders with
+/// percent-encoded values. every param must be used.
+fn substitute_path_params(url: &str, params: &BTreeMap<String, String>) -> Result<String> {
+    let mut url = url.to_string();
+    for (name, value) in params {
+        let value = utf8_percent_encode(value, PATH_SEGMENT).to_string();
+        let braced = format!("{{{name}}}");
+        let colon = format!("/:{name}");
+        let mut found = url.contains(&braced);
+        url = url.replace(&braced, &value);
+
+        // /:name only counts when the name isn't the
+        // start of a longer one, e.g. /:id vs /:idx
+        let mut rest = url.as_str();
+        let mut replaced = String::new();
+        while let Some(i) = rest.find(&colon) {
+            let end = i + colon.len();
+            let boundary = rest[end..].chars().next()
+                .is_none_or(|c| !(c.is_alphanumeric() || c == '_'));
+            replaced.push_str(&rest[..i + 1]);
+            if boundary {
+                replaced.push_str(&value);
+                found = true;
+            } else {
+                replaced.push_str(&rest[i + 1..end]);
+            }
+            rest = &rest[end..];
+        }
+        replaced.push_str(rest);
+        url = replaced;
+
+        if !found {
+            return Err(anyhow!("path param {name} not found in {url}"));
+        }
+    }
+    Ok(url)
+}
+
+/// looks up credentials for the url's host, unless
+/// auth was already supplied some other way.
+fn netrc_credentials(spec: &RequestSpec, url: &Url, has_auth_header: bool) -> Result<Option<netrc::Credentials>> {
+    if spec.auth.is_some() || has_auth_header || !url.username().is_empty() {
+        return Ok(None);
+    }
+    let Some(host) = url.host_str() else {
+        return Ok(None);
+    };
+    let path = match &spec.netrc_file {
+        Some(path) => path.clone(),
+        None => match netrc::default_path() {
+            Some(path) if path.exists() => path,
+            _ => return Ok(None),
+        },
+    };
+    let netrc = Netrc::from_file(&path)?;
+    Ok(netrc.find(host).cloned())
+}
+
+fn add_headers(headers_to_add: &[String]) -> Result<Headers> {
+    let mut headers = Headers::default();
+    for header in headers_to_add {
+        if header.starts_with('@') {
+            // load from file
+            let path = PathBuf::from(header.clone().split_off(1));
+            add_headers_from_file(&mut headers, path)?;
+        } else {
+            let (name, value) = parse_header(header)?;
+            headers.add(name, value);
+        }
+    }
+    Ok(headers)
+}
+
+/// splits Name:value or Name=value on whichever
+/// separator comes first, so values may contain
+/// either. an empty value means unset, which
+/// for accept means sent empty.
+pub(crate) fn parse_header(header: &str) -> Result<(HeaderName, Option<HeaderValue>)> {
+    let (name, value) = header.find([':', '='])
+        .map(|i| (&header[..i], &header[i + 1..]))
+        .ok_or_else(|| anyhow!("malformed header: {:?}", header))?;
+    let name = HeaderName::from_str(name.trim())
+        .with_context(|| format!("bad header name: {:?}", name))?;
+    let value = value.trim();
+    if value.is_empty() {
+        return Ok((name, None));
+    }
+    let value = HeaderValue::from_str(value)
+        .with_context(|| format!("bad value for header {}", name))?;
+    Ok((name, Some(value)))
+}
+
+fn add_headers_from_file(headers: &mut Headers, path: PathBuf) -> Result<()> {
+    let file = fs::File::open(&path)
+        .with_context(|| format!("failed to open header file {:?}", path))?;
+    let header_json: Value = serde_json::from_reader(file)
+        .with_context(|| format!("bad json format from header file: {:?}", path))?;
+    let json_obj = header_json.as_object()
+        .ok_or_else(|| anyhow!("header file {:?} must contain a json object", path))?;
+    for (key, value) in json_obj {
+        let header_key = HeaderName::from_str(key)?;
+        let values = match value {
+            Value::Null => {
+                headers.add(header_key, None);
+                continue;
+            }
+            Value::Array(values) => values.iter().collect(),
+            value => vec![value],
+        };
+        for value in values {
+            let v = match value {
+                Value::String(s) => s.clone(),
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                _ => return Err(anyhow!("{value} is not a proper header value")),
+            };
+            headers.add(header_key.clone(), Some(HeaderValue::from_str(&v)?));
+        }
+    }
+    Ok(())
+}
+
+fn add_cookies(cookies: &str) -> Result<HeaderMap> {
+    let mut header_map = HeaderMap::new();
+    if cookies.starts_with('@') {
+        let (_, cookie_path) = cookies.split_at(1);
+        let path = PathBuf::from(cookie_path);
+        let file_cookies = fs::read_to_string(path)?;
+        header_map.insert(COOKIE, HeaderValue::from_str(&file_cookies)?);
+    } else {
+        header_map.insert(COOKIE, HeaderValue::from_str(cookies)?);
+    }
+    Ok(header_map)
+}
+
+/// methods as plain strings, e.g. "POST".
+mod method_serde {
+    use reqwest::Method;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(method: &Method, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(method.as_str())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Method, D::Error> {
+        let method = String::deserialize(deserializer)?;
+        method.to_uppercase().parse().map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn parse_header_success() {
+        let (name, value) = parse_header("Authorization=Basic abc==").unwrap();
+        assert_eq!(name, AUTHORIZATION);
+        assert_eq!(value.unwrap(), "Basic abc==", "splits on first separator");
+        let (name, value) = parse_header("X-Thing: a=b").unwrap();
+        assert_eq!(name.as_str(), "x-thing", "colon syntax");
+        assert_eq!(value.unwrap(), "a=b");
+        assert_eq!(parse_header("User-Agent:").unwrap(), (USER_AGENT, None), "empty value unsets");
+        assert!(parse_header("nope").is_err(), "missing separator");
+        assert!(parse_header("bad name=x").is_err(), "invalid name");
+    }
+
+    #[test]
+    fn add_headers_repeated_and_unset() {
+        let headers = add_headers(&[
+            "X-Tag=a".to_string(),
+            "X-Tag: b".to_string(),
+            "Accept=text/html".to_string(),
+            "Accept:".to_string(),
+            "User-Agent=".to_string(),
+        ]).unwrap();
+        let tags: Vec<_> = headers.set.get_all("x-tag").iter().collect();
+        assert_eq!(tags, ["a", "b"], "repeated headers are appended");
+        assert!(!headers.set.contains_key("accept"), "later unset removes earlier value");
+        assert!(headers.is_unset(&USER_AGENT));
+    }
+
+    #[test]
+    fn add_headers_from_json_file() {
+        let path = std::env::temp_dir().join(format!("rq-headers-{}.json", std::process::id()));
+        fs::write(&path, r#"{"X-Tag": ["a", "b"], "X-Count": 3, "User-Agent": null}"#).unwrap();
+        let headers = add_headers(&[format!("@{}", path.display())]).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(headers.set.get_all("x-tag").iter().count(), 2);
+        assert_eq!(headers.set.get("x-count").unwrap(), "3");
+        assert!(headers.is_unset(&USER_AGENT));
+    }
+
+    #[test]
+    fn spec_round_trip() {
+        let spec = RequestSpec::new("https://example.com/users")
+            .method(Method::POST)
+            .header("Accept: application/json")
+            .bearer("token")
+            .json(json!({"name": "x"}))
+            .timeout(Duration::from_secs(5));
+        assert_eq!(RequestSpec::from_json(&spec.to_json().unwrap()).unwrap(), spec);
+        assert_eq!(RequestSpec::from_toml(&spec.to_toml().unwrap()).unwrap(), spec);
+
+        let spec = RequestSpec::from_toml("url = \"https://example.com\"\nmethod = \"delete\"\n").unwrap();
+        assert_eq!(spec.method, Method::DELETE);
+        assert!(spec.follow_redirects, "defaults fill in the rest");
+    }
+
+    #[test]
+    fn resolve_url_success() {
+        let url = RequestSpec::new("/users/:id/posts/{post}")
+            .base("https://api.internal/v1/")
+            .path_param("id", "42")
+            .path_param("post", "a b/c")
+            .query("q", "x&y=z")
+            .query("tag", "1")
+            .query("tag", "2")
+            .resolve_url()
+            .unwrap();
+        assert_eq!(url.as_str(), "https://api.internal/v1/users/42/posts/a%20b%2Fc?q=x%26y%3Dz&tag=1&tag=2");
+
+        let url = RequestSpec::new("https://other.com:8080/:id/:idx")
+            .base("https://api.internal")
+            .path_param("id", "1")
+            .resolve_url()
+            .unwrap();
+        assert_eq!(url.as_str(), "https://other.com:8080/1/:idx", "absolute urls ignore base, :idx is untouched");
+
+        assert!(RequestSpec::new("/users").resolve_url().is_err(), "relative without base");
+        assert!(RequestSpec::new("https://a.com/users").path_param("id", "1").resolve_url().is_err(), "unused param");
+    }
+
+    #[test]
+    fn prepare_builds_request() {
+        let spec = RequestSpec::new("https://example.com/a?b=c")
+            .method(Method::PUT)
+            .header("X-Tag: a")
+            .basic("user", Some("pw".to_string()))
+            .body(Body::Text("hi".to_string()));
+        let prepared = spec.prepare().unwrap();
+        let req = prepared.request();
+        assert_eq!(req.method(), Method::PUT);
+        assert_eq!(req.url().as_str(), "https://example.com/a?b=c");
+        assert_eq!(req.headers().get("x-tag").unwrap(), "a");
+        assert!(req.headers().contains_key(AUTHORIZATION));
+        assert_eq!(req.headers().get(USER_AGENT).unwrap(), USER_AGENT_DEFAULT, "the defaults are on the request");
+        assert_eq!(req.headers().get(ACCEPT).unwrap(), "*/*");
+
+        let spec = RequestSpec::new("https://example.com/a")
+            .header("Accept:")
+            .header("Content-Type:")
+            .header("Authorization:")
+            .bearer("t")
+            .json(Value::Null);
+        let prepared = spec.prepare().unwrap();
+        let headers = prepared.request().headers();
+        assert_eq!(headers.get(ACCEPT).unwrap(), "", "sent empty so reqwest doesn't add */*");
+        assert!(!headers.contains_key(CONTENT_TYPE), "unset even when the body sets it");
+        assert!(!headers.contains_key(AUTHORIZATION), "unset even with auth");
+        assert!(RequestSpec::new("not a url").prepare().is_err());
+        let spec = RequestSpec::new("https://example.com/a").timeout(Duration::from_millis(500));
+        assert_eq!(spec.prepare().unwrap().request().timeout(), Some(&Duration::from_millis(500)), "sub-second timeouts are kept");
+        let spec = RequestSpec { timeout_seconds: Some(-1.0), ..RequestSpec::new("https://example.com/a") };
+        assert!(spec.prepare().is_err(), "negative timeout");
+    }
+}
@@ -0,0 +1,131 @@
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use reqwest::header::HeaderValue;
+
+/// how often the progress line is redrawn.
+const REDRAW_EVERY: Duration = Duration::from_millis(100);
+
+/// a streamed request body. a known length is sent as
+/// Content-Length, otherwise the body goes out chunked.
+pub fn body(reader: impl Read + Send + 'static, len: Option<u64>, progress: bool) -> reqwest::blocking::Body {
+    let reader: Box<dyn Read + Send> = if progress {
+        Box::new(Progress::new(reader, io::stderr(), len))
+    } else {
+        Box::new(reader)
+    };
+    match len {
+        Some(len) => reqwest::blocking::Body::sized(reader, len),
+        None => reqwest::blocking::Body::new(reader),
+    }
+}
+
+/// Content-Type from the file extension, if it's one we know.
+pub fn content_type(path: &Path) -> Option<HeaderValue> {
+    let mime = mime_guess::from_path(path).first()?;
+    HeaderValue::from_str(mime.as_ref()).ok()
+}
+
+/// passes reads through, drawing how much
+/// has been sent so far on out.
+struct Progress<R, W: Write> {
+    inner: R,
+    out: W,
+    sent: u64,
+    total: Option<u64>,
+    last_drawn: Option<Instant>,
+    finished: bool,
+}
+
+impl<R: Read, W: Write> Progress<R, W> {
+    fn new(inner: R, out: W, total: Option<u64>) -> Self {
+        Progress { inner, out, sent: 0, total, last_drawn: None, finished: false }
+    }
+
+    fn draw(&mut self, done: bool) {
+        let line = match self.total {
+            Some(total) if total > 0 => format!(
+                "\ruploaded {} / {} ({}%)",
+                human_bytes(self.sent),
+                human_bytes(total),
+                self.sent * 100 / total,
+            ),
+            _ => format!("\ruploaded {}", human_bytes(self.sent)),
+        };
+        // progress is best effort, never fail the upload over it
+        let _ = write!(self.out, "{line}");
+        if done {
+            let _ = writeln!(self.out);
+        }
+        let _ = self.out.flush();
+        self.last_drawn = Some(Instant::now());
+    }
+}
+
+impl<R: Read, W: Write> Read for Progress<R, W> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.sent += n as u64;
+        // sized bodies stop reading at the length
+        // rather than at EOF
+        if n == 0 || Some(self.sent) == self.total {
+            if !self.finished {
+                self.finished = true;
+                self.draw(true);
+            }
+        } else if self.last_drawn.is_none_or(|t| t.elapsed() >= REDRAW_EVERY) {
+            self.draw(false);
+        }
+        Ok(n)
+    }
+}
+
+fn human_bytes(n: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if n < 1024 {
+        return format!("{n} B");
+    }
+    let mut size = n as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_passes_data_through() {
+        let data = vec![7u8; 3000];
+        let mut out = Vec::new();
+        let mut read = Vec::new();
+        Progress::new(&data[..], &mut out, Some(3000)).read_to_end(&mut read).unwrap();
+        assert_eq!(read, data);
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("\ruploaded"), "{out:?}");
+        assert!(out.ends_with("\ruploaded 2.9 KiB / 2.9 KiB (100%)\n"), "{out:?}");
+
+        let mut out = Vec::new();
+        Progress::new(&b"abc"[..], &mut out, None).read_to_end(&mut Vec::new()).unwrap();
+        assert!(String::from_utf8(out).unwrap().ends_with("\ruploaded 3 B\n"), "unknown length");
+    }
+
+    #[test]
+    fn content_type_from_extension() {
+        assert_eq!(content_type(Path::new("a.json")).unwrap(), "application/json");
+        assert_eq!(content_type(Path::new("dir/photo.PNG")).unwrap(), "image/png");
+        assert!(content_type(Path::new("no_extension")).is_none());
+    }
+
+    #[test]
+    fn human_bytes_units() {
+        assert_eq!(human_bytes(512), "512 B");
+        assert_eq!(human_bytes(1536), "1.5 KiB");
+        assert_eq!(human_bytes(5 * 1024 * 1024), "5.0 MiB");
+    }
+}